@@ -0,0 +1,815 @@
+//! Motor de reglas de ajedrez para partidas trustless
+//!
+//! Generador de jugadas legales sin dependencias externas:
+//! - Legalidad (incluye enroque, captura al paso y promoción)
+//! - Jaque, jaque mate y ahogado
+//! - Regla de los 50 movimientos y material insuficiente
+//!
+//! La triple repetición se controla desde el programa con `repetition_key`,
+//! porque requiere el historial de posiciones de la partida.
+
+// Tipos de pieza (bit BLACK marca las piezas negras)
+pub const EMPTY: u8 = 0;
+pub const PAWN: u8 = 1;
+pub const KNIGHT: u8 = 2;
+pub const BISHOP: u8 = 3;
+pub const ROOK: u8 = 4;
+pub const QUEEN: u8 = 5;
+pub const KING: u8 = 6;
+pub const BLACK: u8 = 8;
+
+// Derechos de enroque
+pub const WHITE_KINGSIDE: u8 = 1;
+pub const WHITE_QUEENSIDE: u8 = 2;
+pub const BLACK_KINGSIDE: u8 = 4;
+pub const BLACK_QUEENSIDE: u8 = 8;
+pub const ALL_CASTLING: u8 = 15;
+
+/// Casilla inexistente (sin captura al paso)
+pub const NO_SQUARE: u8 = 64;
+
+/// Tamaño de una posición serializada con `to_bytes`
+pub const POSITION_BYTES: usize = 71;
+
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1),
+];
+const BISHOP_DIRS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const ROOK_DIRS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// Jugada: casillas 0..63 (a1 = 0, h8 = 63) y pieza de promoción (0 si no aplica)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Move {
+    pub from: u8,
+    pub to: u8,
+    pub promotion: u8,
+}
+
+/// Estado de una posición tras aplicar las reglas
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PositionStatus {
+    Ongoing,
+    Checkmate,
+    Stalemate,
+    FiftyMoveRule,
+    InsufficientMaterial,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Position {
+    pub board: [u8; 64],
+    pub white_to_move: bool,
+    pub castling: u8,
+    pub en_passant: u8,
+    pub halfmove_clock: u16,
+    pub fullmove_number: u16,
+}
+
+fn offset(square: u8, file_delta: i8, rank_delta: i8) -> Option<u8> {
+    let file = (square % 8) as i8 + file_delta;
+    let rank = (square / 8) as i8 + rank_delta;
+    if (0..8).contains(&file) && (0..8).contains(&rank) {
+        Some((rank * 8 + file) as u8)
+    } else {
+        None
+    }
+}
+
+fn kind(piece: u8) -> u8 {
+    piece & 7
+}
+
+fn is_white(piece: u8) -> bool {
+    piece != EMPTY && piece & BLACK == 0
+}
+
+fn is_black(piece: u8) -> bool {
+    piece & BLACK != 0
+}
+
+impl Position {
+    /// Posición inicial estándar
+    pub fn initial() -> Self {
+        let mut board = [EMPTY; 64];
+        let back_rank = [ROOK, KNIGHT, BISHOP, QUEEN, KING, BISHOP, KNIGHT, ROOK];
+        for file in 0..8 {
+            board[file] = back_rank[file];
+            board[8 + file] = PAWN;
+            board[48 + file] = PAWN | BLACK;
+            board[56 + file] = back_rank[file] | BLACK;
+        }
+        Position {
+            board,
+            white_to_move: true,
+            castling: ALL_CASTLING,
+            en_passant: NO_SQUARE,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+
+    /// Serializar la posición (tablero, turno, enroques, al paso, relojes)
+    pub fn to_bytes(self) -> [u8; POSITION_BYTES] {
+        let mut bytes = [0u8; POSITION_BYTES];
+        bytes[..64].copy_from_slice(&self.board);
+        bytes[64] = self.white_to_move as u8;
+        bytes[65] = self.castling;
+        bytes[66] = self.en_passant;
+        bytes[67..69].copy_from_slice(&self.halfmove_clock.to_le_bytes());
+        bytes[69..71].copy_from_slice(&self.fullmove_number.to_le_bytes());
+        bytes
+    }
+
+    /// Deserializar y validar una posición
+    /// Devuelve None si las piezas o los campos no son válidos
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != POSITION_BYTES || bytes[64] > 1 || bytes[65] > ALL_CASTLING {
+            return None;
+        }
+        let mut board = [EMPTY; 64];
+        board.copy_from_slice(&bytes[..64]);
+
+        let mut white_kings = 0;
+        let mut black_kings = 0;
+        for (square, &piece) in board.iter().enumerate() {
+            if piece == EMPTY {
+                continue;
+            }
+            if piece & !(BLACK | 7) != 0 || !(PAWN..=KING).contains(&kind(piece)) {
+                return None;
+            }
+            // No puede haber peones en la primera ni en la última fila
+            if kind(piece) == PAWN && !(8..56).contains(&square) {
+                return None;
+            }
+            if piece == KING {
+                white_kings += 1;
+            } else if piece == KING | BLACK {
+                black_kings += 1;
+            }
+        }
+        if white_kings != 1 || black_kings != 1 {
+            return None;
+        }
+
+        // La casilla al paso debe venir de un avance doble del rival:
+        // vacía, con la de origen vacía y el peón rival justo delante
+        let en_passant = bytes[66];
+        if en_passant != NO_SQUARE {
+            let white_to_move = bytes[64] == 1;
+            let (ep_rank, forward, enemy_pawn) = if white_to_move {
+                (40..48, -1, PAWN | BLACK)
+            } else {
+                (16..24, 1, PAWN)
+            };
+            if !ep_rank.contains(&en_passant) {
+                return None;
+            }
+            let pushed = offset(en_passant, 0, forward)?;
+            let origin = offset(en_passant, 0, -forward)?;
+            if board[en_passant as usize] != EMPTY
+                || board[origin as usize] != EMPTY
+                || board[pushed as usize] != enemy_pawn
+            {
+                return None;
+            }
+        }
+
+        Some(Position {
+            board,
+            white_to_move: bytes[64] == 1,
+            castling: bytes[65],
+            en_passant,
+            halfmove_clock: u16::from_le_bytes([bytes[67], bytes[68]]),
+            fullmove_number: u16::from_le_bytes([bytes[69], bytes[70]]),
+        })
+    }
+
+    /// Clave de la posición para detectar repeticiones
+    /// (tablero, turno, enroques y captura al paso solo si es jugable)
+    pub fn repetition_key(&self) -> [u8; 67] {
+        let mut key = [0u8; 67];
+        key[..64].copy_from_slice(&self.board);
+        key[64] = self.white_to_move as u8;
+        key[65] = self.castling;
+        key[66] = if self.has_en_passant_capture() { self.en_passant } else { NO_SQUARE };
+        key
+    }
+
+    fn own(&self, piece: u8) -> bool {
+        if self.white_to_move { is_white(piece) } else { is_black(piece) }
+    }
+
+    fn enemy(&self, piece: u8) -> bool {
+        if self.white_to_move { is_black(piece) } else { is_white(piece) }
+    }
+
+    fn king_square(&self, white: bool) -> Option<u8> {
+        let king = if white { KING } else { KING | BLACK };
+        self.board.iter().position(|&p| p == king).map(|s| s as u8)
+    }
+
+    /// Indica si `square` está atacada por el bando indicado
+    pub fn is_square_attacked(&self, square: u8, by_white: bool) -> bool {
+        let color = if by_white { 0 } else { BLACK };
+
+        // Peones: un peón atacante está una fila "detrás" de la casilla
+        let pawn_rank_delta = if by_white { -1 } else { 1 };
+        for file_delta in [-1, 1] {
+            if let Some(from) = offset(square, file_delta, pawn_rank_delta) {
+                if self.board[from as usize] == PAWN | color {
+                    return true;
+                }
+            }
+        }
+
+        for &(df, dr) in KNIGHT_OFFSETS.iter() {
+            if let Some(from) = offset(square, df, dr) {
+                if self.board[from as usize] == KNIGHT | color {
+                    return true;
+                }
+            }
+        }
+
+        for &(df, dr) in KING_OFFSETS.iter() {
+            if let Some(from) = offset(square, df, dr) {
+                if self.board[from as usize] == KING | color {
+                    return true;
+                }
+            }
+        }
+
+        for (dirs, slider) in [(BISHOP_DIRS, BISHOP), (ROOK_DIRS, ROOK)] {
+            for &(df, dr) in dirs.iter() {
+                let mut current = square;
+                while let Some(next) = offset(current, df, dr) {
+                    let piece = self.board[next as usize];
+                    if piece != EMPTY {
+                        if piece == slider | color || piece == QUEEN | color {
+                            return true;
+                        }
+                        break;
+                    }
+                    current = next;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Indica si el bando que mueve está en jaque
+    pub fn in_check(&self) -> bool {
+        match self.king_square(self.white_to_move) {
+            Some(king) => self.is_square_attacked(king, !self.white_to_move),
+            None => false,
+        }
+    }
+
+    fn push_pawn_move(moves: &mut Vec<Move>, from: u8, to: u8) {
+        let promotion_rank = to / 8 == 0 || to / 8 == 7;
+        if promotion_rank {
+            for promotion in [QUEEN, ROOK, BISHOP, KNIGHT] {
+                moves.push(Move { from, to, promotion });
+            }
+        } else {
+            moves.push(Move { from, to, promotion: 0 });
+        }
+    }
+
+    /// Jugadas pseudo-legales (pueden dejar al propio rey en jaque)
+    fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(64);
+        let forward: i8 = if self.white_to_move { 1 } else { -1 };
+        let start_rank = if self.white_to_move { 1 } else { 6 };
+
+        for from in 0..64u8 {
+            let piece = self.board[from as usize];
+            if !self.own(piece) {
+                continue;
+            }
+            match kind(piece) {
+                PAWN => {
+                    if let Some(one) = offset(from, 0, forward) {
+                        if self.board[one as usize] == EMPTY {
+                            Self::push_pawn_move(&mut moves, from, one);
+                            if from / 8 == start_rank {
+                                if let Some(two) = offset(one, 0, forward) {
+                                    if self.board[two as usize] == EMPTY {
+                                        moves.push(Move { from, to: two, promotion: 0 });
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    for file_delta in [-1, 1] {
+                        if let Some(to) = offset(from, file_delta, forward) {
+                            if self.enemy(self.board[to as usize]) {
+                                Self::push_pawn_move(&mut moves, from, to);
+                            } else if to == self.en_passant {
+                                moves.push(Move { from, to, promotion: 0 });
+                            }
+                        }
+                    }
+                }
+                KNIGHT | KING => {
+                    let offsets = if kind(piece) == KNIGHT { KNIGHT_OFFSETS } else { KING_OFFSETS };
+                    for &(df, dr) in offsets.iter() {
+                        if let Some(to) = offset(from, df, dr) {
+                            if !self.own(self.board[to as usize]) {
+                                moves.push(Move { from, to, promotion: 0 });
+                            }
+                        }
+                    }
+                }
+                BISHOP | ROOK | QUEEN => {
+                    let mut dirs: Vec<(i8, i8)> = Vec::with_capacity(8);
+                    if kind(piece) != ROOK {
+                        dirs.extend_from_slice(&BISHOP_DIRS);
+                    }
+                    if kind(piece) != BISHOP {
+                        dirs.extend_from_slice(&ROOK_DIRS);
+                    }
+                    for (df, dr) in dirs {
+                        let mut current = from;
+                        while let Some(to) = offset(current, df, dr) {
+                            let target = self.board[to as usize];
+                            if self.own(target) {
+                                break;
+                            }
+                            moves.push(Move { from, to, promotion: 0 });
+                            if target != EMPTY {
+                                break;
+                            }
+                            current = to;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.push_castling_moves(&mut moves);
+        moves
+    }
+
+    fn push_castling_moves(&self, moves: &mut Vec<Move>) {
+        let (base, kingside, queenside, color) = if self.white_to_move {
+            (0u8, WHITE_KINGSIDE, WHITE_QUEENSIDE, 0)
+        } else {
+            (56u8, BLACK_KINGSIDE, BLACK_QUEENSIDE, BLACK)
+        };
+        let king_from = base + 4;
+        if self.board[king_from as usize] != KING | color {
+            return;
+        }
+        let enemy_is_white = !self.white_to_move;
+        if (self.castling & (kingside | queenside)) == 0
+            || self.is_square_attacked(king_from, enemy_is_white)
+        {
+            return;
+        }
+
+        if self.castling & kingside != 0
+            && self.board[(base + 7) as usize] == ROOK | color
+            && self.board[(base + 5) as usize] == EMPTY
+            && self.board[(base + 6) as usize] == EMPTY
+            && !self.is_square_attacked(base + 5, enemy_is_white)
+            && !self.is_square_attacked(base + 6, enemy_is_white)
+        {
+            moves.push(Move { from: king_from, to: base + 6, promotion: 0 });
+        }
+
+        if self.castling & queenside != 0
+            && self.board[base as usize] == ROOK | color
+            && self.board[(base + 1) as usize] == EMPTY
+            && self.board[(base + 2) as usize] == EMPTY
+            && self.board[(base + 3) as usize] == EMPTY
+            && !self.is_square_attacked(base + 3, enemy_is_white)
+            && !self.is_square_attacked(base + 2, enemy_is_white)
+        {
+            moves.push(Move { from: king_from, to: base + 2, promotion: 0 });
+        }
+    }
+
+    /// Aplicar una jugada sin validar su legalidad
+    fn make_move(&mut self, mv: Move) {
+        let piece = self.board[mv.from as usize];
+        let captured = self.board[mv.to as usize];
+        let color = piece & BLACK;
+        let forward: i8 = if self.white_to_move { 1 } else { -1 };
+
+        if kind(piece) == PAWN || captured != EMPTY {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+
+        // Captura al paso: el peón capturado está detrás de la casilla destino
+        if kind(piece) == PAWN && mv.to == self.en_passant && captured == EMPTY {
+            if let Some(victim) = offset(mv.to, 0, -forward) {
+                self.board[victim as usize] = EMPTY;
+            }
+            self.halfmove_clock = 0;
+        }
+
+        self.board[mv.to as usize] = if mv.promotion != 0 { mv.promotion | color } else { piece };
+        self.board[mv.from as usize] = EMPTY;
+
+        // Enroque: mover también la torre
+        if kind(piece) == KING && mv.from.abs_diff(mv.to) == 2 {
+            let base = mv.from - 4;
+            let (rook_from, rook_to) = if mv.to > mv.from { (base + 7, base + 5) } else { (base, base + 3) };
+            self.board[rook_to as usize] = self.board[rook_from as usize];
+            self.board[rook_from as usize] = EMPTY;
+        }
+
+        // Actualizar derechos de enroque
+        if kind(piece) == KING {
+            self.castling &= if color == 0 {
+                !(WHITE_KINGSIDE | WHITE_QUEENSIDE)
+            } else {
+                !(BLACK_KINGSIDE | BLACK_QUEENSIDE)
+            };
+        }
+        for square in [mv.from, mv.to] {
+            match square {
+                0 => self.castling &= !WHITE_QUEENSIDE,
+                7 => self.castling &= !WHITE_KINGSIDE,
+                56 => self.castling &= !BLACK_QUEENSIDE,
+                63 => self.castling &= !BLACK_KINGSIDE,
+                _ => {}
+            }
+        }
+
+        self.en_passant = if kind(piece) == PAWN && mv.from.abs_diff(mv.to) == 16 {
+            offset(mv.from, 0, forward).unwrap_or(NO_SQUARE)
+        } else {
+            NO_SQUARE
+        };
+
+        if !self.white_to_move {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
+        self.white_to_move = !self.white_to_move;
+    }
+
+    /// Jugadas legales del bando que mueve
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.pseudo_legal_moves()
+            .into_iter()
+            .filter(|&mv| self.leaves_king_safe(mv))
+            .collect()
+    }
+
+    fn leaves_king_safe(&self, mv: Move) -> bool {
+        let mut next = *self;
+        next.make_move(mv);
+        match next.king_square(self.white_to_move) {
+            Some(king) => !next.is_square_attacked(king, next.white_to_move),
+            None => false,
+        }
+    }
+
+    fn has_legal_move(&self) -> bool {
+        self.pseudo_legal_moves()
+            .into_iter()
+            .any(|mv| self.leaves_king_safe(mv))
+    }
+
+    fn has_en_passant_capture(&self) -> bool {
+        if self.en_passant == NO_SQUARE {
+            return false;
+        }
+        self.pseudo_legal_moves().into_iter().any(|mv| {
+            mv.to == self.en_passant
+                && kind(self.board[mv.from as usize]) == PAWN
+                && self.leaves_king_safe(mv)
+        })
+    }
+
+    /// Indica si la jugada es legal en esta posición
+    pub fn is_legal(&self, mv: Move) -> bool {
+        mv.from < 64
+            && mv.to < 64
+            && self.own(self.board[mv.from as usize])
+            && self.pseudo_legal_moves().contains(&mv)
+            && self.leaves_king_safe(mv)
+    }
+
+    /// Aplicar una jugada validando su legalidad
+    /// Devuelve false (sin modificar la posición) si la jugada es ilegal
+    pub fn play(&mut self, mv: Move) -> bool {
+        if !self.is_legal(mv) {
+            return false;
+        }
+        self.make_move(mv);
+        true
+    }
+
+    /// Material insuficiente para dar mate: K vs K, K+menor vs K, K+A vs K+A del mismo color
+    fn insufficient_material(&self) -> bool {
+        let mut minors = Vec::with_capacity(2);
+        for (square, &piece) in self.board.iter().enumerate() {
+            match kind(piece) {
+                EMPTY | KING => {}
+                KNIGHT | BISHOP => {
+                    if minors.len() == 2 {
+                        return false;
+                    }
+                    minors.push((kind(piece), piece & BLACK, square));
+                }
+                _ => return false,
+            }
+        }
+        match minors.as_slice() {
+            [] | [_] => true,
+            [(BISHOP, _, s1), (BISHOP, _, s2)] => {
+                let square_color = |s: usize| (s / 8 + s % 8) % 2;
+                square_color(*s1) == square_color(*s2)
+            }
+            _ => false,
+        }
+    }
+
+    /// Estado de la posición para el bando que mueve
+    pub fn status(&self) -> PositionStatus {
+        if !self.has_legal_move() {
+            return if self.in_check() { PositionStatus::Checkmate } else { PositionStatus::Stalemate };
+        }
+        if self.halfmove_clock >= 100 {
+            return PositionStatus::FiftyMoveRule;
+        }
+        if self.insufficient_material() {
+            return PositionStatus::InsufficientMaterial;
+        }
+        PositionStatus::Ongoing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Posición a partir de un FEN (solo para tests)
+    fn fen(fen: &str) -> Position {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let mut board = [EMPTY; 64];
+        for (row, rank) in fields[0].split('/').enumerate() {
+            let mut file = 0;
+            for c in rank.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    file += skip as usize;
+                    continue;
+                }
+                let piece = match c.to_ascii_lowercase() {
+                    'p' => PAWN,
+                    'n' => KNIGHT,
+                    'b' => BISHOP,
+                    'r' => ROOK,
+                    'q' => QUEEN,
+                    'k' => KING,
+                    _ => panic!("pieza inválida {}", c),
+                };
+                board[(7 - row) * 8 + file] = if c.is_ascii_lowercase() { piece | BLACK } else { piece };
+                file += 1;
+            }
+        }
+        let mut castling = 0;
+        for c in fields[2].chars() {
+            castling |= match c {
+                'K' => WHITE_KINGSIDE,
+                'Q' => WHITE_QUEENSIDE,
+                'k' => BLACK_KINGSIDE,
+                'q' => BLACK_QUEENSIDE,
+                _ => 0,
+            };
+        }
+        Position {
+            board,
+            white_to_move: fields[1] == "w",
+            castling,
+            en_passant: if fields[3] == "-" { NO_SQUARE } else { sq(fields[3]) },
+            halfmove_clock: fields.get(4).map_or(0, |f| f.parse().unwrap()),
+            fullmove_number: fields.get(5).map_or(1, |f| f.parse().unwrap()),
+        }
+    }
+
+    /// Casilla en notación algebraica ("e4")
+    fn sq(name: &str) -> u8 {
+        let bytes = name.as_bytes();
+        (bytes[1] - b'1') * 8 + (bytes[0] - b'a')
+    }
+
+    fn mv(from: &str, to: &str) -> Move {
+        Move { from: sq(from), to: sq(to), promotion: 0 }
+    }
+
+    fn perft(position: &Position, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = position.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves.into_iter().map(|m| {
+            let mut next = *position;
+            assert!(next.play(m));
+            perft(&next, depth - 1)
+        }).sum()
+    }
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -";
+
+    #[test]
+    fn perft_initial_position() {
+        let start = Position::initial();
+        assert_eq!(start, fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"));
+        assert_eq!(perft(&start, 1), 20);
+        assert_eq!(perft(&start, 2), 400);
+        assert_eq!(perft(&start, 3), 8902);
+        assert_eq!(perft(&start, 4), 197281);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        let position = fen(KIWIPETE);
+        assert_eq!(perft(&position, 1), 48);
+        assert_eq!(perft(&position, 2), 2039);
+        assert_eq!(perft(&position, 3), 97862);
+    }
+
+    #[test]
+    fn castling_requires_safe_king_path() {
+        // Sin amenazas se puede enrocar a ambos lados
+        let position = fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq -");
+        assert!(position.is_legal(mv("e1", "g1")));
+        assert!(position.is_legal(mv("e1", "c1")));
+
+        // No se enroca estando en jaque
+        let position = fen("r3k2r/8/8/8/4r3/8/8/R3K2R w KQkq -");
+        assert!(!position.is_legal(mv("e1", "g1")));
+        assert!(!position.is_legal(mv("e1", "c1")));
+
+        // Ni cruzando una casilla atacada (f1 y d1)
+        let position = fen("r3k2r/8/8/8/8/8/5r2/R3K2R w KQkq -");
+        assert!(!position.is_legal(mv("e1", "g1")));
+        let position = fen("r3k2r/8/8/8/8/8/3r4/R3K2R w KQkq -");
+        assert!(!position.is_legal(mv("e1", "c1")));
+
+        // b1 atacada no impide el enroque largo (el rey no pasa por ella)
+        let position = fen("1r2k2r/8/8/8/8/8/8/R3K2R w KQk -");
+        assert!(position.is_legal(mv("e1", "c1")));
+
+        // El enroque mueve la torre y quita los derechos
+        let mut position = fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq -");
+        assert!(position.play(mv("e1", "g1")));
+        assert_eq!(position.board[sq("f1") as usize], ROOK);
+        assert_eq!(position.board[sq("h1") as usize], EMPTY);
+        assert_eq!(position.castling, BLACK_KINGSIDE | BLACK_QUEENSIDE);
+    }
+
+    #[test]
+    fn en_passant_cannot_expose_king() {
+        // Tras ...d7-d5 capturar al paso dejaría al rey blanco en jaque por la fila
+        let position = fen("8/8/8/K2pP2r/8/8/8/4k3 w - d6");
+        assert!(!position.is_legal(mv("e5", "d6")));
+        assert_eq!(position.repetition_key()[66], NO_SQUARE);
+
+        // Sin la torre la captura es legal y retira el peón capturado
+        let mut position = fen("8/8/8/K2pP3/8/8/8/4k3 w - d6");
+        assert!(position.play(mv("e5", "d6")));
+        assert_eq!(position.board[sq("d5") as usize], EMPTY);
+        assert_eq!(position.board[sq("d6") as usize], PAWN);
+        assert_eq!(position.halfmove_clock, 0);
+    }
+
+    #[test]
+    fn underpromotion() {
+        let position = fen("8/P7/8/8/8/8/8/k1K5 w - -");
+        let promotions: Vec<u8> = position.legal_moves().into_iter()
+            .filter(|m| m.from == sq("a7"))
+            .map(|m| m.promotion)
+            .collect();
+        assert_eq!(promotions.len(), 4);
+        for piece in [QUEEN, ROOK, BISHOP, KNIGHT] {
+            assert!(promotions.contains(&piece));
+        }
+        // Sin pieza de promoción la jugada no es legal
+        assert!(!position.is_legal(mv("a7", "a8")));
+
+        let mut promoted = position;
+        assert!(promoted.play(Move { from: sq("a7"), to: sq("a8"), promotion: KNIGHT }));
+        assert_eq!(promoted.board[sq("a8") as usize], KNIGHT);
+
+        // Promoción a caballo negro dando jaque
+        let mut position = fen("8/8/8/8/8/8/1K1p4/7k b - -");
+        assert!(position.play(Move { from: sq("d2"), to: sq("d1"), promotion: KNIGHT }));
+        assert_eq!(position.board[sq("d1") as usize], KNIGHT | BLACK);
+        assert!(position.in_check());
+    }
+
+    #[test]
+    fn stalemate_and_checkmate() {
+        let stalemate = fen("7k/5Q2/6K1/8/8/8/8/8 b - -");
+        assert!(!stalemate.in_check());
+        assert_eq!(stalemate.status(), PositionStatus::Stalemate);
+
+        let mate = fen("7k/6Q1/6K1/8/8/8/8/8 b - -");
+        assert!(mate.in_check());
+        assert_eq!(mate.status(), PositionStatus::Checkmate);
+
+        // Mate del pastor desde la posición inicial
+        let mut position = Position::initial();
+        for (from, to) in [("e2", "e4"), ("e7", "e5"), ("f1", "c4"), ("b8", "c6"),
+                           ("d1", "h5"), ("g8", "f6"), ("h5", "f7")] {
+            assert!(position.play(mv(from, to)));
+        }
+        assert_eq!(position.status(), PositionStatus::Checkmate);
+    }
+
+    #[test]
+    fn fifty_move_rule() {
+        let mut position = fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80");
+        assert_eq!(position.status(), PositionStatus::Ongoing);
+        assert!(position.play(mv("a1", "a2")));
+        assert_eq!(position.halfmove_clock, 100);
+        assert_eq!(position.status(), PositionStatus::FiftyMoveRule);
+
+        // Un movimiento de peón reinicia el contador
+        let mut position = fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80");
+        assert!(position.play(mv("e2", "e4")));
+        assert_eq!(position.halfmove_clock, 0);
+        assert_eq!(position.status(), PositionStatus::Ongoing);
+
+        // El mate tiene prioridad sobre la regla de los 50 movimientos
+        let mut position = fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80");
+        assert!(position.play(mv("a1", "a8")));
+        assert_eq!(position.status(), PositionStatus::Checkmate);
+    }
+
+    #[test]
+    fn insufficient_material() {
+        for (position, insufficient) in [
+            ("4k3/8/8/8/8/8/8/4K3 w - -", true),
+            ("4k3/8/8/8/8/8/8/4KN2 w - -", true),
+            ("4k3/8/8/8/8/8/8/4KB2 w - -", true),
+            // Alfiles del mismo color (c8 y f1 son casillas claras)
+            ("2b1k3/8/8/8/8/8/8/4KB2 w - -", true),
+            // Alfiles de distinto color
+            ("3bk3/8/8/8/8/8/8/4KB2 w - -", false),
+            ("4k3/8/8/8/8/8/8/3NKN2 w - -", false),
+            ("4k3/8/8/8/8/8/4P3/4K3 w - -", false),
+            ("4k3/8/8/8/8/8/8/4K2R w - -", false),
+        ] {
+            assert_eq!(
+                fen(position).status() == PositionStatus::InsufficientMaterial,
+                insufficient,
+                "{}",
+                position
+            );
+        }
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let position = fen(KIWIPETE);
+        assert_eq!(Position::from_bytes(&position.to_bytes()), Some(position));
+        assert_eq!(Position::from_bytes(&position.to_bytes()[..70]), None);
+    }
+
+    #[test]
+    fn from_bytes_rejects_bad_en_passant() {
+        let valid = fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6");
+        assert!(Position::from_bytes(&valid.to_bytes()).is_some());
+
+        // Fuera de la tercera o sexta fila
+        let mut bytes = valid.to_bytes();
+        bytes[66] = sq("d5");
+        assert!(Position::from_bytes(&bytes).is_none());
+        bytes[66] = 65;
+        assert!(Position::from_bytes(&bytes).is_none());
+
+        // Fila que no corresponde al bando que mueve
+        let wrong_side = fen("4k3/8/8/8/3P4/8/8/4K3 w - d3");
+        assert!(Position::from_bytes(&wrong_side.to_bytes()).is_none());
+
+        // Sin el peón que acaba de avanzar dos casillas
+        let no_pawn = fen("4k3/8/8/4P3/8/8/8/4K3 w - d6");
+        assert!(Position::from_bytes(&no_pawn.to_bytes()).is_none());
+
+        // Casilla de captura o de origen ocupadas
+        let occupied = fen("4k3/3n4/8/3pP3/8/8/8/4K3 w - d6");
+        assert!(Position::from_bytes(&occupied.to_bytes()).is_none());
+        let occupied = fen("4k3/8/3n4/3pP3/8/8/8/4K3 w - d6");
+        assert!(Position::from_bytes(&occupied.to_bytes()).is_none());
+
+        // El caso de las negras
+        let black = fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3");
+        assert!(Position::from_bytes(&black.to_bytes()).is_some());
+    }
+}
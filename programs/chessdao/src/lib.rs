@@ -1,5 +1,19 @@
+// Los handlers de Anchor reciben los argumentos de la instrucción por separado
+#![allow(clippy::too_many_arguments)]
+
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, MintTo, Burn, Transfer};
+use anchor_lang::Discriminator;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::hash::{hash, hashv};
+use anchor_lang::solana_program::sysvar::instructions::{
+    self as sysvar_instructions, load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_spl::token_interface::{
+    self, Burn, CloseAccount, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked,
+};
+
+pub mod chess;
+use chess::{Move as ChessMove, Position, PositionStatus};
 
 declare_id!("CHESSxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx");
 
@@ -41,6 +55,18 @@ pub mod chessdao {
         state.created_at = Clock::get()?.unix_timestamp;
         state.last_mint_reset = Clock::get()?.unix_timestamp;
         state.minted_today = 0;
+        state.treasury = Pubkey::default();
+        state.game_fee_basis_points = DEFAULT_GAME_FEE_BASIS_POINTS;
+        state.draw_fee_basis_points = DEFAULT_GAME_FEE_BASIS_POINTS;
+        state.fee_tiers = Vec::new();
+        state.fee_split = FeeSplit {
+            treasury_bps: 10000,
+            burn_bps: 0,
+            staking_bps: 0,
+            referrer_bps: 0,
+        };
+        state.staking_pool = Pubkey::default();
+        state.version = TOKEN_STATE_VERSION;
         
         emit!(ProgramInitialized {
             authority: ctx.accounts.authority.key(),
@@ -104,7 +130,7 @@ pub mod chessdao {
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
         
-        token::mint_to(cpi_ctx, amount)?;
+        token_interface::mint_to(cpi_ctx, amount)?;
         
         // Actualizar estado
         state.current_supply = state.current_supply.checked_add(amount).unwrap();
@@ -141,7 +167,7 @@ pub mod chessdao {
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
         
-        token::burn(cpi_ctx, amount)?;
+        token_interface::burn(cpi_ctx, amount)?;
         
         // Actualizar supply
         state.current_supply = state.current_supply.checked_sub(amount).unwrap();
@@ -159,6 +185,7 @@ pub mod chessdao {
 
     /// Swap CHESS tokens por $GAME (off-chain credits)
     /// Registra el swap on-chain para transparencia
+    /// La parte en CHESS del fee se reparte según `fee_split` (referrer opcional)
     pub fn swap_chess_to_game(
         ctx: Context<SwapChessToGame>,
         chess_amount: u64,
@@ -187,16 +214,75 @@ pub mod chessdao {
         
         let net_game_amount = gross_game_amount.checked_sub(fee).unwrap();
         
-        // Transferir CHESS al treasury (burn o hold)
-        let cpi_accounts = Transfer {
+        // Parte en CHESS correspondiente al fee, repartida según la configuración
+        let chess_fee = chess_amount
+            .checked_mul(state.swap_fee_basis_points as u64)
+            .unwrap()
+            .checked_div(10000)
+            .unwrap();
+        let fee_distribution = split_fee(
+            &state.fee_split,
+            chess_fee,
+            state.staking_pool != Pubkey::default(),
+            ctx.accounts.referrer_token_account.is_some(),
+        );
+        
+        if fee_distribution.burned > 0 {
+            let cpi_accounts = Burn {
+                mint: ctx.accounts.mint.to_account_info(),
+                from: ctx.accounts.user_token_account.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+            token_interface::burn(cpi_ctx, fee_distribution.burned)?;
+        }
+        
+        if fee_distribution.staking > 0 {
+            let staking = ctx.accounts.staking_token_account.as_ref()
+                .ok_or(ErrorCode::MissingFeeAccount)?;
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.user_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: staking.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+            token_interface::transfer_checked(cpi_ctx, fee_distribution.staking, ctx.accounts.mint.decimals)?;
+        }
+        
+        if let Some(referrer) = &ctx.accounts.referrer_token_account {
+            if fee_distribution.referrer > 0 {
+                let cpi_accounts = TransferChecked {
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: referrer.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+                token_interface::transfer_checked(cpi_ctx, fee_distribution.referrer, ctx.accounts.mint.decimals)?;
+            }
+        }
+        
+        // El resto del CHESS (principal + parte del treasury) va al treasury
+        let treasury_amount = chess_amount
+            - fee_distribution.burned
+            - fee_distribution.staking
+            - fee_distribution.referrer;
+        let cpi_accounts = TransferChecked {
             from: ctx.accounts.user_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
             to: ctx.accounts.treasury_token_account.to_account_info(),
             authority: ctx.accounts.user.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
         
-        token::transfer(cpi_ctx, chess_amount)?;
+        token_interface::transfer_checked(cpi_ctx, treasury_amount, ctx.accounts.mint.decimals)?;
+        
+        // Los CHESS quemados salen del supply
+        let state = &mut ctx.accounts.token_state;
+        state.current_supply = state.current_supply
+            .saturating_sub(fee_distribution.burned);
         
         // Registrar swap para que el backend acredite $GAME
         let swap_record = &mut ctx.accounts.swap_record;
@@ -216,6 +302,7 @@ pub mod chessdao {
             to_token: "GAME".to_string(),
             to_amount: net_game_amount,
             fee,
+            fee_distribution,
             timestamp: Clock::get()?.unix_timestamp,
         });
         
@@ -355,6 +442,7 @@ pub mod chessdao {
         ctx: Context<AdminAction>,
         new_daily_limit: Option<u64>,
         new_swap_fee: Option<u16>,
+        new_treasury: Option<Pubkey>,
     ) -> Result<()> {
         let state = &mut ctx.accounts.token_state;
         
@@ -369,9 +457,98 @@ pub mod chessdao {
             state.swap_fee_basis_points = fee;
         }
         
+        // Treasury global de CHESS (los fees de partidas van al treasury de cada WagerMint)
+        if let Some(treasury) = new_treasury {
+            state.treasury = treasury;
+        }
+        
         emit!(ConfigUpdated {
             daily_mint_limit: state.daily_mint_limit,
             swap_fee_basis_points: state.swap_fee_basis_points,
+            treasury: state.treasury,
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Actualizar la tabla de fees de partidas
+    /// Solo authority. Los tramos deben ir ordenados por min_stake estrictamente creciente
+    pub fn update_game_fees(
+        ctx: Context<AdminAction>,
+        new_game_fee: Option<u16>,
+        new_draw_fee: Option<u16>,
+        new_fee_tiers: Option<Vec<FeeTier>>,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.token_state;
+        
+        require!(ctx.accounts.authority.key() == state.authority, ErrorCode::Unauthorized);
+        
+        if let Some(fee) = new_game_fee {
+            require!(fee <= MAX_GAME_FEE_BASIS_POINTS, ErrorCode::FeeTooHigh);
+            state.game_fee_basis_points = fee;
+        }
+        
+        if let Some(fee) = new_draw_fee {
+            require!(fee <= MAX_GAME_FEE_BASIS_POINTS, ErrorCode::FeeTooHigh);
+            state.draw_fee_basis_points = fee;
+        }
+        
+        if let Some(tiers) = new_fee_tiers {
+            require!(tiers.len() <= MAX_FEE_TIERS, ErrorCode::TooManyFeeTiers);
+            require!(
+                tiers.iter().all(|tier| tier.fee_basis_points <= MAX_GAME_FEE_BASIS_POINTS),
+                ErrorCode::FeeTooHigh
+            );
+            require!(
+                tiers.windows(2).all(|pair| pair[0].min_stake < pair[1].min_stake),
+                ErrorCode::InvalidFeeTiers
+            );
+            state.fee_tiers = tiers;
+        }
+        
+        emit!(GameFeesUpdated {
+            game_fee_basis_points: state.game_fee_basis_points,
+            draw_fee_basis_points: state.draw_fee_basis_points,
+            fee_tiers: state.fee_tiers.clone(),
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Configurar el reparto de fees de partidas y swaps
+    /// Solo authority. Los pesos deben sumar 10000; staking requiere un pool configurado
+    pub fn update_fee_split(
+        ctx: Context<AdminAction>,
+        new_split: FeeSplit,
+        new_staking_pool: Option<Pubkey>,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.token_state;
+        
+        require!(ctx.accounts.authority.key() == state.authority, ErrorCode::Unauthorized);
+        
+        let total = new_split.treasury_bps as u32
+            + new_split.burn_bps as u32
+            + new_split.staking_bps as u32
+            + new_split.referrer_bps as u32;
+        require!(total == 10000, ErrorCode::InvalidFeeSplit);
+        
+        if let Some(pool) = new_staking_pool {
+            state.staking_pool = pool;
+        }
+        require!(
+            new_split.staking_bps == 0 || state.staking_pool != Pubkey::default(),
+            ErrorCode::InvalidStakingPool
+        );
+        
+        state.fee_split = new_split;
+        
+        emit!(FeeSplitUpdated {
+            split: state.fee_split,
+            staking_pool: state.staking_pool,
             by: ctx.accounts.authority.key(),
             timestamp: Clock::get()?.unix_timestamp,
         });
@@ -379,24 +556,175 @@ pub mod chessdao {
         Ok(())
     }
 
+    /// Añadir o actualizar un mint admitido para apuestas (USDC, wSOL, CHESS...)
+    /// Solo authority. `treasury` y `staking_pool` son cuentas de token de ese mint;
+    /// un staking_pool por defecto envía la parte de staking al treasury
+    pub fn configure_wager_mint(
+        ctx: Context<ConfigureWagerMint>,
+        min_stake: u64,
+        max_stake: u64,
+        treasury: Pubkey,
+        staking_pool: Pubkey,
+        enabled: bool,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.authority.key() == ctx.accounts.token_state.authority,
+            ErrorCode::Unauthorized
+        );
+        require!(min_stake > 0 && min_stake <= max_stake, ErrorCode::InvalidStakeLimits);
+        require!(treasury != Pubkey::default(), ErrorCode::InvalidTreasury);
+        
+        let wager_mint = &mut ctx.accounts.wager_mint;
+        wager_mint.mint = ctx.accounts.mint.key();
+        wager_mint.treasury = treasury;
+        wager_mint.staking_pool = staking_pool;
+        wager_mint.min_stake = min_stake;
+        wager_mint.max_stake = max_stake;
+        wager_mint.enabled = enabled;
+        
+        emit!(WagerMintConfigured {
+            mint: wager_mint.mint,
+            treasury,
+            staking_pool,
+            min_stake,
+            max_stake,
+            enabled,
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Wager mint {} configured (enabled: {})", wager_mint.mint, enabled);
+        Ok(())
+    }
+
+    /// Migrar `token_state` al layout actual
+    /// Las cuentas creadas antes de los fees de partida (versión 0) no tienen sitio
+    /// para los campos nuevos: se amplía la cuenta y se rellenan con los valores por defecto
+    /// de `initialize`. Solo authority, que paga la renta adicional
+    pub fn migrate_token_state(ctx: Context<MigrateTokenState>) -> Result<()> {
+        let info = ctx.accounts.token_state.to_account_info();
+        let legacy = {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() >= 8 && data[..8] == TokenState::DISCRIMINATOR,
+                ErrorCode::InvalidTokenStateLayout
+            );
+            require!(data.len() != 8 + TokenState::INIT_SPACE, ErrorCode::TokenStateUpToDate);
+            require!(data.len() == 8 + TokenStateV0::INIT_SPACE, ErrorCode::InvalidTokenStateLayout);
+            TokenStateV0::deserialize(&mut &data[8..])?
+        };
+        require!(ctx.accounts.authority.key() == legacy.authority, ErrorCode::Unauthorized);
+        
+        // Completar la renta del nuevo tamaño antes de ampliar la cuenta
+        let new_len = 8 + TokenState::INIT_SPACE;
+        let rent_due = Rent::get()?.minimum_balance(new_len).saturating_sub(info.lamports());
+        if rent_due > 0 {
+            let cpi_accounts = anchor_lang::system_program::Transfer {
+                from: ctx.accounts.authority.to_account_info(),
+                to: info.clone(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+            anchor_lang::system_program::transfer(cpi_ctx, rent_due)?;
+        }
+        info.realloc(new_len, true)?;
+        
+        let state = TokenState {
+            is_initialized: legacy.is_initialized,
+            authority: legacy.authority,
+            pending_authority: legacy.pending_authority,
+            max_supply: legacy.max_supply,
+            current_supply: legacy.current_supply,
+            daily_mint_limit: legacy.daily_mint_limit,
+            minted_today: legacy.minted_today,
+            last_mint_reset: legacy.last_mint_reset,
+            swap_fee_basis_points: legacy.swap_fee_basis_points,
+            chess_to_game_rate: legacy.chess_to_game_rate,
+            is_paused: legacy.is_paused,
+            created_at: legacy.created_at,
+            treasury: Pubkey::default(),
+            game_fee_basis_points: DEFAULT_GAME_FEE_BASIS_POINTS,
+            draw_fee_basis_points: DEFAULT_GAME_FEE_BASIS_POINTS,
+            fee_tiers: Vec::new(),
+            fee_split: FeeSplit {
+                treasury_bps: 10000,
+                burn_bps: 0,
+                staking_bps: 0,
+                referrer_bps: 0,
+            },
+            staking_pool: Pubkey::default(),
+            version: TOKEN_STATE_VERSION,
+        };
+        let mut data = info.try_borrow_mut_data()?;
+        state.try_serialize(&mut &mut data[..])?;
+        
+        emit!(TokenStateMigrated {
+            from_version: 0,
+            to_version: TOKEN_STATE_VERSION,
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Token state migrated to version {}", TOKEN_STATE_VERSION);
+        Ok(())
+    }
+
     // ============= GAME ESCROW MODULE =============
     // Sistema de apuestas on-chain con escrow seguro
 
     /// Crear una nueva partida con apuesta
     /// El creador deposita tokens en escrow
+    /// `game_id` lo elige el cliente (p. ej. aleatorio); si ya existe, la creación falla
+    /// Opcional: `referrer` recibe su parte del fee al liquidarse la partida
+    /// Opcional: `opponent_stake` distinto de bet_amount para partidas con hándicap
+    /// (por defecto ambos jugadores apuestan lo mismo)
+    /// Opcional: partida privada para un oponente concreto (invited_opponent)
+    /// y/o protegida por un secreto de invitación (invite_hash = sha256(secreto))
     pub fn create_game(
         ctx: Context<CreateGame>,
+        game_id: u64,
         bet_amount: u64,
-        time_control: String,
+        time_control: TimeControl,
         game_title: String,
+        mode: GameMode,
+        invited_opponent: Option<Pubkey>,
+        invite_hash: Option<[u8; 32]>,
+        join_window_seconds: Option<i64>,
+        referrer: Option<Pubkey>,
+        opponent_stake: Option<u64>,
     ) -> Result<()> {
         let state = &ctx.accounts.token_state;
         let game = &mut ctx.accounts.game_match;
         
         require!(!state.is_paused, ErrorCode::ProgramPaused);
         require!(bet_amount > 0, ErrorCode::InvalidAmount);
-        require!(time_control.len() <= 10, ErrorCode::InvalidTimeControl);
+        require!(
+            bet_amount >= ctx.accounts.wager_mint.min_stake
+                && bet_amount <= ctx.accounts.wager_mint.max_stake,
+            ErrorCode::StakeOutOfRange
+        );
+        let player2_stake = opponent_stake.unwrap_or(bet_amount);
+        require!(
+            player2_stake >= ctx.accounts.wager_mint.min_stake
+                && player2_stake <= ctx.accounts.wager_mint.max_stake,
+            ErrorCode::StakeOutOfRange
+        );
+        require!(is_valid_time_control(&time_control), ErrorCode::InvalidTimeControl);
         require!(game_title.len() <= 50, ErrorCode::TitleTooLong);
+        require!(
+            invited_opponent != Some(ctx.accounts.player1.key()),
+            ErrorCode::CannotJoinOwnGame
+        );
+        
+        require!(
+            referrer != Some(ctx.accounts.player1.key()),
+            ErrorCode::SelfReferral
+        );
+        
+        let join_window = join_window_seconds.unwrap_or(DEFAULT_JOIN_WINDOW_SECONDS);
+        require!(
+            (MIN_JOIN_WINDOW_SECONDS..=MAX_JOIN_WINDOW_SECONDS).contains(&join_window),
+            ErrorCode::InvalidJoinWindow
+        );
         
         // Verificar blacklist
         require!(
@@ -405,29 +733,65 @@ pub mod chessdao {
         );
         
         // Transferir tokens al escrow
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from: ctx.accounts.player1_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
             to: ctx.accounts.escrow_token_account.to_account_info(),
             authority: ctx.accounts.player1.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, bet_amount)?;
+        token_interface::transfer_checked(cpi_ctx, bet_amount, ctx.accounts.mint.decimals)?;
+        
+        // Con mints Token-2022 con transfer fee llega menos que bet_amount:
+        // el bote se basa en lo que realmente hay en el escrow
+        ctx.accounts.escrow_token_account.reload()?;
+        let deposited = ctx.accounts.escrow_token_account.amount;
         
         // Inicializar partida
-        game.game_id = ctx.accounts.game_counter.total_games;
+        game.game_id = game_id;
         game.player1 = ctx.accounts.player1.key();
         game.player2 = Pubkey::default();
         game.bet_amount = bet_amount;
-        game.total_pot = bet_amount;
-        game.time_control = time_control.clone();
+        game.total_pot = deposited;
+        game.player1_deposit = deposited;
+        game.player2_deposit = 0;
+        game.player2_stake = player2_stake;
+        game.time_control = time_control;
         game.title = game_title.clone();
         game.status = GameStatus::Waiting;
+        game.mode = mode;
         game.winner = None;
         game.created_at = Clock::get()?.unix_timestamp;
         game.started_at = 0;
         game.ended_at = 0;
+        game.player1_time_left = time_control.base_seconds as i64;
+        game.player2_time_left = time_control.base_seconds as i64;
+        game.player1_to_move = true;
+        game.clock_running_since = 0;
+        game.player1_last_active = 0;
+        game.player2_last_active = 0;
+        game.record = None;
+        game.draw_offered_by = None;
+        game.moves_played = 0;
+        game.invited_opponent = invited_opponent;
+        game.invite_hash = invite_hash;
+        game.expires_at = game.created_at.checked_add(join_window).unwrap();
+        game.referrer = referrer;
+        game.series_id = None;
+        game.previous_game = None;
+        game.rematch_game_id = None;
+        game.arena_id = None;
+        game.team_battle_id = None;
+        game.mint = ctx.accounts.mint.key();
         game.escrow_bump = ctx.bumps.escrow_token_account;
+        game.rent_payer = ctx.accounts.player1.key();
+        game.player1_result = None;
+        game.player2_result = None;
+        game.proposed_result = None;
+        game.settlement_deadline = 0;
+        game.disputed_by = None;
+        game.dispute_bond = 0;
         
         // Incrementar contador de juegos
         ctx.accounts.game_counter.total_games += 1;
@@ -436,25 +800,49 @@ pub mod chessdao {
         emit!(GameCreated {
             game_id: game.game_id,
             player1: ctx.accounts.player1.key(),
+            mint: ctx.accounts.mint.key(),
             bet_amount,
+            player2_stake,
             time_control,
             title: game_title,
+            mode,
+            invited_opponent,
+            is_private: invited_opponent.is_some() || invite_hash.is_some(),
+            expires_at: game.expires_at,
             timestamp: Clock::get()?.unix_timestamp,
         });
         
-        msg!("Game {} created with {} CHESS bet", game.game_id, bet_amount);
+        msg!("Game {} created with {} bet in mint {}", game.game_id, bet_amount, game.mint);
         Ok(())
     }
 
     /// Unirse a una partida existente
     /// Player2 deposita la misma cantidad en escrow
-    pub fn join_game(ctx: Context<JoinGame>) -> Result<()> {
+    /// En partidas privadas solo el invitado (o quien conozca el secreto) puede unirse
+    pub fn join_game(ctx: Context<JoinGame>, invite_secret: Option<Vec<u8>>) -> Result<()> {
         let state = &ctx.accounts.token_state;
         let game = &mut ctx.accounts.game_match;
         
         require!(!state.is_paused, ErrorCode::ProgramPaused);
         require!(game.status == GameStatus::Waiting, ErrorCode::GameNotWaiting);
         require!(game.player1 != ctx.accounts.player2.key(), ErrorCode::CannotJoinOwnGame);
+        require!(game.previous_game.is_none(), ErrorCode::RematchGame);
+        require!(
+            Clock::get()?.unix_timestamp <= game.expires_at,
+            ErrorCode::GameExpired
+        );
+        
+        // Verificar invitación
+        if let Some(invited) = game.invited_opponent {
+            require!(invited == ctx.accounts.player2.key(), ErrorCode::NotInvited);
+        }
+        if let Some(invite_hash) = game.invite_hash {
+            let secret = invite_secret.ok_or(ErrorCode::InvalidInviteSecret)?;
+            require!(
+                hash(&secret).to_bytes() == invite_hash,
+                ErrorCode::InvalidInviteSecret
+            );
+        }
         
         // Verificar blacklist
         require!(
@@ -463,21 +851,35 @@ pub mod chessdao {
         );
         
         // Transferir tokens al escrow
-        let cpi_accounts = Transfer {
+        let escrow_before = ctx.accounts.escrow_token_account.amount;
+        let cpi_accounts = TransferChecked {
             from: ctx.accounts.player2_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
             to: ctx.accounts.escrow_token_account.to_account_info(),
             authority: ctx.accounts.player2.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, game.bet_amount)?;
+        token_interface::transfer_checked(cpi_ctx, game.player2_stake, ctx.accounts.mint.decimals)?;
+        
+        // Contar solo lo que llegó al escrow (transfer fee de Token-2022)
+        ctx.accounts.escrow_token_account.reload()?;
+        let deposited = ctx.accounts.escrow_token_account.amount
+            .checked_sub(escrow_before).unwrap();
         
         // Actualizar partida
         game.player2 = ctx.accounts.player2.key();
-        game.total_pot = game.bet_amount.checked_mul(2).unwrap();
+        game.player2_deposit = deposited;
+        game.total_pot = game.player1_deposit.checked_add(deposited).unwrap();
         game.status = GameStatus::Active;
         game.started_at = Clock::get()?.unix_timestamp;
         
+        // Arrancar el reloj de blancas (player1)
+        game.player1_to_move = true;
+        game.clock_running_since = game.started_at;
+        game.player1_last_active = game.started_at;
+        game.player2_last_active = game.started_at;
+        
         emit!(GameJoined {
             game_id: game.game_id,
             player1: game.player1,
@@ -486,148 +888,70 @@ pub mod chessdao {
             timestamp: Clock::get()?.unix_timestamp,
         });
         
-        msg!("Player {} joined game {}. Total pot: {} CHESS", 
+        msg!("Player {} joined game {}. Total pot: {}", 
             ctx.accounts.player2.key(), game.game_id, game.total_pot);
         Ok(())
     }
 
     /// Resolver partida - distribuir premios
-    /// Solo puede ser llamado por authority (backend/oracle)
+    /// Vía de emergencia de authority: solo con la liquidación optimista desactivada
+    /// y siempre pagando a las cuentas de los propios jugadores
     pub fn resolve_game(
         ctx: Context<ResolveGame>,
         winner_key: Pubkey,
         is_draw: bool,
+        record: Option<GameRecord>,
     ) -> Result<()> {
         let game = &mut ctx.accounts.game_match;
         let state = &ctx.accounts.token_state;
         
         require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(game.mode == GameMode::Standard, ErrorCode::TrustlessGame);
         require!(
             ctx.accounts.authority.key() == state.authority,
             ErrorCode::Unauthorized
         );
+        // Con liquidación optimista el resultado debe pasar por la ventana de disputa
+        require!(
+            !ctx.accounts.game_config.optimistic_settlement,
+            ErrorCode::OptimisticSettlementRequired
+        );
+        // Las apuestas grandes solo se liquidan con el quórum de oráculos
+        require!(
+            !requires_oracle_quorum(&ctx.accounts.oracle_registry, game),
+            ErrorCode::QuorumRequired
+        );
         
         let current_time = Clock::get()?.unix_timestamp;
-        game.ended_at = current_time;
-        
-        // Calcular fee del DAO (2.5% = 250 basis points)
-        let dao_fee_basis_points: u64 = 250;
-        let dao_fee = game.total_pot
-            .checked_mul(dao_fee_basis_points).unwrap()
-            .checked_div(10000).unwrap();
-        let prize_pool = game.total_pot.checked_sub(dao_fee).unwrap();
-        
-        // Seeds para firmar como escrow PDA
-        let game_id_bytes = game.game_id.to_le_bytes();
-        let seeds = &[
-            b"game_escrow",
-            game_id_bytes.as_ref(),
-            &[game.escrow_bump],
-        ];
-        let signer_seeds = &[&seeds[..]];
-        
-        if is_draw {
-            // Empate: cada jugador recibe 50% del prize pool
-            game.status = GameStatus::Draw;
-            game.winner = None;
-            
-            let half_prize = prize_pool.checked_div(2).unwrap();
-            
-            // Transferir a player1
-            let cpi_accounts1 = Transfer {
-                from: ctx.accounts.escrow_token_account.to_account_info(),
-                to: ctx.accounts.player1_token_account.to_account_info(),
-                authority: ctx.accounts.escrow_token_account.to_account_info(),
-            };
-            let cpi_ctx1 = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts1,
-                signer_seeds,
-            );
-            token::transfer(cpi_ctx1, half_prize)?;
-            
-            // Transferir a player2
-            let cpi_accounts2 = Transfer {
-                from: ctx.accounts.escrow_token_account.to_account_info(),
-                to: ctx.accounts.player2_token_account.to_account_info(),
-                authority: ctx.accounts.escrow_token_account.to_account_info(),
-            };
-            let cpi_ctx2 = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts2,
-                signer_seeds,
-            );
-            token::transfer(cpi_ctx2, half_prize)?;
-            
-            emit!(GameResolved {
-                game_id: game.game_id,
-                winner: None,
-                is_draw: true,
-                prize_amount: half_prize,
-                dao_fee,
-                timestamp: current_time,
-            });
-        } else {
-            // Victoria: ganador recibe prize pool completo
-            require!(
-                winner_key == game.player1 || winner_key == game.player2,
-                ErrorCode::InvalidWinner
-            );
-            
-            game.status = GameStatus::Completed;
-            game.winner = Some(winner_key);
-            
-            // Determinar cuenta destino
-            let winner_token_account = if winner_key == game.player1 {
-                ctx.accounts.player1_token_account.to_account_info()
-            } else {
-                ctx.accounts.player2_token_account.to_account_info()
-            };
-            
-            // Transferir premio al ganador
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.escrow_token_account.to_account_info(),
-                to: winner_token_account,
-                authority: ctx.accounts.escrow_token_account.to_account_info(),
-            };
-            let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts,
-                signer_seeds,
-            );
-            token::transfer(cpi_ctx, prize_pool)?;
-            
-            emit!(GameResolved {
-                game_id: game.game_id,
-                winner: Some(winner_key),
-                is_draw: false,
-                prize_amount: prize_pool,
-                dao_fee,
-                timestamp: current_time,
-            });
-        }
+        game.record = record;
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        let (prize_amount, dao_fee, fee_basis_points, fee_distribution) = settle_game(game, &payout, &ctx.accounts.token_state, winner_key, is_draw, current_time)?;
         
-        // Transferir fee al treasury
-        if dao_fee > 0 {
-            let cpi_accounts_fee = Transfer {
-                from: ctx.accounts.escrow_token_account.to_account_info(),
-                to: ctx.accounts.treasury_token_account.to_account_info(),
-                authority: ctx.accounts.escrow_token_account.to_account_info(),
-            };
-            let cpi_ctx_fee = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts_fee,
-                signer_seeds,
-            );
-            token::transfer(cpi_ctx_fee, dao_fee)?;
-        }
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: game.winner,
+            is_draw,
+            prize_amount,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            record: game.record,
+            timestamp: current_time,
+        });
         
         // Decrementar juegos activos
         ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
-            .active_games.checked_sub(1).unwrap_or(0);
+            .active_games.saturating_sub(1);
         
         msg!("Game {} resolved. Winner: {:?}, Prize: {}, Fee: {}", 
-            game.game_id, game.winner, prize_pool, dao_fee);
+            game.game_id, game.winner, prize_amount, dao_fee);
         Ok(())
     }
 
@@ -638,51 +962,193 @@ pub mod chessdao {
         
         require!(game.status == GameStatus::Waiting, ErrorCode::GameNotWaiting);
         require!(game.player1 == ctx.accounts.player1.key(), ErrorCode::Unauthorized);
-        
-        // Seeds para firmar como escrow PDA
-        let game_id_bytes = game.game_id.to_le_bytes();
-        let seeds = &[
-            b"game_escrow",
-            game_id_bytes.as_ref(),
-            &[game.escrow_bump],
-        ];
-        let signer_seeds = &[&seeds[..]];
+        require!(game.player2_deposit == 0, ErrorCode::OpponentAlreadyDeposited);
         
         // Reembolsar a player1
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.escrow_token_account.to_account_info(),
-            to: ctx.accounts.player1_token_account.to_account_info(),
-            authority: ctx.accounts.escrow_token_account.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            cpi_accounts,
-            signer_seeds,
-        );
-        token::transfer(cpi_ctx, game.bet_amount)?;
+        transfer_from_escrow_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.mint,
+            game,
+            ctx.accounts.player1_token_account.to_account_info(),
+            game.player1_deposit,
+        )?;
         
         game.status = GameStatus::Cancelled;
         game.ended_at = Clock::get()?.unix_timestamp;
         
         // Decrementar juegos activos
         ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
-            .active_games.checked_sub(1).unwrap_or(0);
+            .active_games.saturating_sub(1);
         
         emit!(GameCancelled {
             game_id: game.game_id,
             player1: game.player1,
-            refund_amount: game.bet_amount,
+            refund_amount: game.player1_deposit,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Game {} cancelled. Refunded {} to player1", game.game_id, game.player1_deposit);
+        Ok(())
+    }
+
+    /// Expirar una partida sin oponente tras su fecha límite
+    /// Cualquiera puede ejecutarla: reembolsa a player1, cierra el escrow y la partida
+    /// y paga al cranker una recompensa opcional con la renta recuperada
+    pub fn expire_game(ctx: Context<ExpireGame>) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let config = &ctx.accounts.game_config;
+        
+        require!(game.status == GameStatus::Waiting, ErrorCode::GameNotWaiting);
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(current_time > game.expires_at, ErrorCode::GameNotExpired);
+        
+        // En una revancha a medio financiar puede haber depositado player2
+        let player2_refund = game.player2_deposit;
+        if player2_refund > 0 {
+            let player2_token_account = ctx.accounts.player2_token_account.as_ref()
+                .ok_or(ErrorCode::InvalidTokenAccount)?;
+            transfer_from_escrow_account(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &ctx.accounts.mint,
+                game,
+                player2_token_account.to_account_info(),
+                player2_refund,
+            )?;
+        }
+        
+        // Reembolsar el resto a player1 y cerrar el escrow (la renta vuelve a quien lo pagó)
+        let refund_amount = ctx.accounts.escrow_token_account.amount - player2_refund;
+        if refund_amount > 0 {
+            transfer_from_escrow_account(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &ctx.accounts.mint,
+                game,
+                ctx.accounts.player1_token_account.to_account_info(),
+                refund_amount,
+            )?;
+        }
+        close_escrow_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            game,
+            ctx.accounts.rent_payer.to_account_info(),
+        )?;
+        
+        // El tablero de una partida trustless se cierra con ella
+        if game.mode == GameMode::Trustless {
+            let board = ctx.accounts.chess_board.as_ref()
+                .ok_or(ErrorCode::BoardRequired)?;
+            close_board(board, ctx.accounts.player1.to_account_info())?;
+        }
+        
+        game.status = GameStatus::Cancelled;
+        game.ended_at = current_time;
+        
+        // Recompensa para el cranker desde la renta de la cuenta de la partida;
+        // el resto vuelve a quien la pagó al cerrarse la cuenta
+        let game_info = game.to_account_info();
+        let bounty = config.expiry_bounty_lamports.min(game_info.lamports());
+        if bounty > 0 {
+            **game_info.try_borrow_mut_lamports()? -= bounty;
+            **ctx.accounts.cranker.to_account_info().try_borrow_mut_lamports()? += bounty;
+        }
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        emit!(GameExpired {
+            game_id: game.game_id,
+            player1: game.player1,
+            refund_amount,
+            player2_refund,
+            cranker: ctx.accounts.cranker.key(),
+            bounty,
+            timestamp: current_time,
+        });
+        
+        msg!("Game {} expired. Refunded {} to player1, bounty {} lamports", 
+            game.game_id, refund_amount, bounty);
+        Ok(())
+    }
+
+    /// Cerrar una partida ya terminada para recuperar la renta
+    /// Cualquiera puede ejecutarla: el polvo que quede en el escrow va al treasury.
+    /// La renta de la partida y de su escrow vuelve a quien la pagó (player1, el
+    /// organizador de la arena o el líder de la batalla) y la del tablero a player1
+    pub fn close_game(ctx: Context<CloseGame>) -> Result<()> {
+        let game = &ctx.accounts.game_match;
+        
+        require!(is_game_finished(game.status), ErrorCode::GameNotFinished);
+        
+        let mut rent_reclaimed = game.to_account_info().lamports();
+        let mut dust = 0;
+        if has_game_escrow(game) {
+            let escrow = ctx.accounts.escrow_token_account.as_ref()
+                .ok_or(ErrorCode::InvalidTokenAccount)?;
+            
+            // Barrer cualquier resto del escrow al treasury antes de cerrarlo
+            dust = escrow.amount;
+            if dust > 0 {
+                transfer_from_escrow_account(
+                    &ctx.accounts.token_program,
+                    escrow,
+                    &ctx.accounts.mint,
+                    game,
+                    ctx.accounts.treasury_token_account.to_account_info(),
+                    dust,
+                )?;
+            }
+            
+            rent_reclaimed += escrow.to_account_info().lamports();
+            close_escrow_account(
+                &ctx.accounts.token_program,
+                escrow,
+                game,
+                ctx.accounts.rent_payer.to_account_info(),
+            )?;
+        }
+        
+        // El tablero de las partidas trustless también lo pagó player1
+        if game.mode == GameMode::Trustless {
+            let board = ctx.accounts.chess_board.as_ref()
+                .ok_or(ErrorCode::BoardRequired)?;
+            rent_reclaimed += close_board(board, ctx.accounts.player1.to_account_info())?;
+        }
+        
+        // Evento de archivo con todo lo necesario para reconstruir la partida off-chain
+        emit!(GameArchived {
+            game_id: game.game_id,
+            player1: game.player1,
+            player2: game.player2,
+            bet_amount: game.bet_amount,
+            total_pot: game.total_pot,
+            status: game.status,
+            mode: game.mode,
+            winner: game.winner,
+            record: game.record,
+            created_at: game.created_at,
+            ended_at: game.ended_at,
+            escrow_dust: dust,
+            rent_reclaimed,
+            closed_by: ctx.accounts.closer.key(),
             timestamp: Clock::get()?.unix_timestamp,
         });
         
-        msg!("Game {} cancelled. Refunded {} CHESS to player1", game.game_id, game.bet_amount);
+        msg!("Game {} closed. Reclaimed {} lamports", game.game_id, rent_reclaimed);
         Ok(())
     }
 
-    /// Reclamar fondos por timeout
-    /// Si oponente abandona, el otro jugador puede reclamar después de 30 min
+    /// Reclamar fondos por abandono
+    /// Solo el jugador cuyo oponente lleva inactivo más del umbral
+    /// (derivado del control de tiempo) puede reclamar
     pub fn timeout_claim(ctx: Context<TimeoutClaim>) -> Result<()> {
         let game = &mut ctx.accounts.game_match;
+        let config = &ctx.accounts.game_config;
         let claimer = ctx.accounts.claimer.key();
         
         require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
@@ -691,57 +1157,51 @@ pub mod chessdao {
             ErrorCode::NotAPlayer
         );
         
-        // Verificar timeout (30 minutos = 1800 segundos)
-        let timeout_seconds: i64 = 1800;
+        // Verificar inactividad del oponente
+        let (claimer_last_active, opponent_last_active) = if claimer == game.player1 {
+            (game.player1_last_active, game.player2_last_active)
+        } else {
+            (game.player2_last_active, game.player1_last_active)
+        };
+        require!(
+            claimer_last_active >= opponent_last_active,
+            ErrorCode::ClaimerInactive
+        );
+        
+        let timeout_seconds = abandonment_threshold(config, &game.time_control);
         let current_time = Clock::get()?.unix_timestamp;
         require!(
-            current_time - game.started_at >= timeout_seconds,
+            current_time - opponent_last_active >= timeout_seconds,
             ErrorCode::TimeoutNotReached
         );
         
         // Calcular fee del DAO
-        let dao_fee_basis_points: u64 = 250;
+        let fee_basis_points = game_fee_basis_points(&ctx.accounts.token_state, game.largest_stake(), false);
         let dao_fee = game.total_pot
-            .checked_mul(dao_fee_basis_points).unwrap()
+            .checked_mul(fee_basis_points as u64).unwrap()
             .checked_div(10000).unwrap();
         let prize_pool = game.total_pot.checked_sub(dao_fee).unwrap();
         
-        // Seeds para firmar como escrow PDA
-        let game_id_bytes = game.game_id.to_le_bytes();
-        let seeds = &[
-            b"game_escrow",
-            game_id_bytes.as_ref(),
-            &[game.escrow_bump],
-        ];
-        let signer_seeds = &[&seeds[..]];
-        
         // Transferir premio al claimer
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.escrow_token_account.to_account_info(),
-            to: ctx.accounts.claimer_token_account.to_account_info(),
-            authority: ctx.accounts.escrow_token_account.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            cpi_accounts,
-            signer_seeds,
-        );
-        token::transfer(cpi_ctx, prize_pool)?;
-        
-        // Transferir fee al treasury
-        if dao_fee > 0 {
-            let cpi_accounts_fee = Transfer {
-                from: ctx.accounts.escrow_token_account.to_account_info(),
-                to: ctx.accounts.treasury_token_account.to_account_info(),
-                authority: ctx.accounts.escrow_token_account.to_account_info(),
-            };
-            let cpi_ctx_fee = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts_fee,
-                signer_seeds,
-            );
-            token::transfer(cpi_ctx_fee, dao_fee)?;
-        }
+        transfer_from_escrow_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.mint,
+            game,
+            ctx.accounts.claimer_token_account.to_account_info(),
+            prize_pool,
+        )?;
+        
+        // Repartir el fee entre sus destinos
+        let fee_distribution = distribute_escrow_fee(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.fees,
+            game,
+            &ctx.accounts.token_state.fee_split,
+            dao_fee,
+        )?;
         
         game.status = GameStatus::Timeout;
         game.winner = Some(claimer);
@@ -749,13 +1209,15 @@ pub mod chessdao {
         
         // Decrementar juegos activos
         ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
-            .active_games.checked_sub(1).unwrap_or(0);
+            .active_games.saturating_sub(1);
         
         emit!(GameTimeout {
             game_id: game.game_id,
             claimer,
             prize_amount: prize_pool,
             dao_fee,
+            fee_basis_points,
+            fee_distribution,
             timestamp: current_time,
         });
         
@@ -763,548 +1225,8079 @@ pub mod chessdao {
             game.game_id, claimer, prize_pool);
         Ok(())
     }
-}
-
-// ============= ACCOUNTS =============
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + TokenState::INIT_SPACE,
-        seeds = [b"token_state"],
-        bump
-    )]
-    pub token_state: Account<'info, TokenState>,
-    
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + Blacklist::INIT_SPACE,
-        seeds = [b"blacklist"],
-        bump
-    )]
-    pub blacklist: Account<'info, Blacklist>,
+    /// Declarar resultado firmado por un jugador
+    /// Si ambos jugadores declaran el mismo resultado se liquida sin authority.
+    /// Si no coinciden, authority resuelve con resolve_game
+    pub fn submit_result(
+        ctx: Context<SubmitResult>,
+        winner_key: Pubkey,
+        is_draw: bool,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let state = &ctx.accounts.token_state;
+        let player = ctx.accounts.player.key();
+        
+        require!(!state.is_paused, ErrorCode::ProgramPaused);
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            player == game.player1 || player == game.player2,
+            ErrorCode::NotAPlayer
+        );
+        require!(
+            is_draw || winner_key == game.player1 || winner_key == game.player2,
+            ErrorCode::InvalidWinner
+        );
+        
+        let claim = ResultClaim {
+            winner: if is_draw { None } else { Some(winner_key) },
+            is_draw,
+        };
+        
+        // Un jugador puede corregir su declaración mientras la partida siga activa
+        if player == game.player1 {
+            game.player1_result = Some(claim);
+        } else {
+            game.player2_result = Some(claim);
+        }
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        emit!(ResultSubmitted {
+            game_id: game.game_id,
+            player,
+            winner: claim.winner,
+            is_draw,
+            timestamp: current_time,
+        });
+        
+        let (player1_result, player2_result) = match (game.player1_result, game.player2_result) {
+            (Some(r1), Some(r2)) => (r1, r2),
+            _ => {
+                msg!("Game {}: result submitted by {}, waiting for opponent", game.game_id, player);
+                return Ok(());
+            }
+        };
+        
+        if player1_result != player2_result {
+            emit!(ResultDisputed {
+                game_id: game.game_id,
+                player1_winner: player1_result.winner,
+                player1_is_draw: player1_result.is_draw,
+                player2_winner: player2_result.winner,
+                player2_is_draw: player2_result.is_draw,
+                timestamp: current_time,
+            });
+            
+            msg!("Game {}: players disagree on the result, authority must resolve", game.game_id);
+            return Ok(());
+        }
+        
+        // Ambos jugadores coinciden: liquidar igual que resolve_game
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        let (prize_amount, dao_fee, fee_basis_points, fee_distribution) = settle_game(game, &payout, &ctx.accounts.token_state, winner_key, is_draw, current_time)?;
+        
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: game.winner,
+            is_draw,
+            prize_amount,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            record: game.record,
+            timestamp: current_time,
+        });
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        msg!("Game {} settled by both players. Winner: {:?}, Prize: {}, Fee: {}", 
+            game.game_id, game.winner, prize_amount, dao_fee);
+        Ok(())
+    }
+
+    // ============= ORACLE MODULE =============
+    // Registro de oráculos de resultados, separado de la authority del token
+
+    /// Crear el registro de oráculos
+    /// Solo authority puede ejecutar; el admin del registro puede ser otra clave (cold wallet)
+    pub fn initialize_oracle_registry(
+        ctx: Context<InitializeOracleRegistry>,
+        admin: Pubkey,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let registry = &mut ctx.accounts.oracle_registry;
+        
+        require!(ctx.accounts.authority.key() == state.authority, ErrorCode::Unauthorized);
+        
+        registry.admin = admin;
+        registry.oracles = Vec::new();
+        registry.quorum_bet_threshold = 0;
+        registry.quorum_size = 1;
+        
+        emit!(OracleRegistryInitialized {
+            admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Oracle registry initialized. Admin: {}", admin);
+        Ok(())
+    }
+
+    /// Registrar un nuevo oráculo de resultados
+    pub fn add_oracle(ctx: Context<ManageOracles>, oracle: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.oracle_registry;
+        
+        require!(ctx.accounts.admin.key() == registry.admin, ErrorCode::Unauthorized);
+        require!(!registry.oracles.contains(&oracle), ErrorCode::OracleAlreadyRegistered);
+        require!(registry.oracles.len() < MAX_ORACLES, ErrorCode::OracleRegistryFull);
+        
+        registry.oracles.push(oracle);
+        
+        emit!(OracleAdded {
+            oracle,
+            by: ctx.accounts.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Oracle {} added to registry", oracle);
+        Ok(())
+    }
+
+    /// Rotar la clave de un oráculo existente
+    pub fn rotate_oracle(
+        ctx: Context<ManageOracles>,
+        old_oracle: Pubkey,
+        new_oracle: Pubkey,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.oracle_registry;
+        
+        require!(ctx.accounts.admin.key() == registry.admin, ErrorCode::Unauthorized);
+        require!(!registry.oracles.contains(&new_oracle), ErrorCode::OracleAlreadyRegistered);
+        
+        let index = registry.oracles.iter()
+            .position(|&o| o == old_oracle)
+            .ok_or(ErrorCode::OracleNotRegistered)?;
+        registry.oracles[index] = new_oracle;
+        
+        emit!(OracleRotated {
+            old_oracle,
+            new_oracle,
+            by: ctx.accounts.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Oracle {} rotated to {}", old_oracle, new_oracle);
+        Ok(())
+    }
+
+    /// Revocar un oráculo (por ejemplo, si su clave se filtró)
+    pub fn revoke_oracle(ctx: Context<ManageOracles>, oracle: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.oracle_registry;
+        
+        require!(ctx.accounts.admin.key() == registry.admin, ErrorCode::Unauthorized);
+        
+        let index = registry.oracles.iter()
+            .position(|&o| o == oracle)
+            .ok_or(ErrorCode::OracleNotRegistered)?;
+        registry.oracles.remove(index);
+        
+        emit!(OracleRevoked {
+            oracle,
+            by: ctx.accounts.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Oracle {} revoked", oracle);
+        Ok(())
+    }
+
+    /// Resolver partida con un resultado firmado por un oráculo registrado
+    /// La transacción debe incluir, justo antes, una instrucción Ed25519
+    /// que verifique la firma del oráculo sobre el mensaje del resultado.
+    /// Cualquiera puede enviar la transacción (relayer)
+    pub fn resolve_game_with_oracle(
+        ctx: Context<ResolveGameWithOracle>,
+        winner_key: Pubkey,
+        is_draw: bool,
+        expiry: i64,
+        oracle: Pubkey,
+        record: Option<GameRecord>,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let registry = &ctx.accounts.oracle_registry;
+        
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(game.mode == GameMode::Standard, ErrorCode::TrustlessGame);
+        require!(registry.oracles.contains(&oracle), ErrorCode::OracleNotRegistered);
+        require!(!requires_oracle_quorum(registry, game), ErrorCode::QuorumRequired);
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(current_time <= expiry, ErrorCode::ResultExpired);
+        
+        // Verificar firma Ed25519 del oráculo
+        let message = oracle_result_message(game.game_id, winner_key, is_draw, expiry, &record);
+        verify_ed25519_signature(&ctx.accounts.instructions_sysvar, &oracle, &message)?;
+        
+        game.record = record;
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        let (prize_amount, dao_fee, fee_basis_points, fee_distribution) = settle_game(game, &payout, &ctx.accounts.token_state, winner_key, is_draw, current_time)?;
+        
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: game.winner,
+            is_draw,
+            prize_amount,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            record: game.record,
+            timestamp: current_time,
+        });
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        msg!("Game {} resolved by oracle {}. Winner: {:?}, Prize: {}, Fee: {}", 
+            game.game_id, oracle, game.winner, prize_amount, dao_fee);
+        Ok(())
+    }
+
+    /// Configurar el quórum M-de-N para partidas de apuesta alta
+    /// quorum_bet_threshold = 0 desactiva el quórum
+    pub fn set_oracle_quorum(
+        ctx: Context<ManageOracles>,
+        quorum_bet_threshold: u64,
+        quorum_size: u8,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.oracle_registry;
+        
+        require!(ctx.accounts.admin.key() == registry.admin, ErrorCode::Unauthorized);
+        require!(
+            quorum_size >= 1 && (quorum_size as usize) <= registry.oracles.len(),
+            ErrorCode::InvalidQuorum
+        );
+        
+        registry.quorum_bet_threshold = quorum_bet_threshold;
+        registry.quorum_size = quorum_size;
+        
+        emit!(OracleQuorumUpdated {
+            quorum_bet_threshold,
+            quorum_size,
+            total_oracles: registry.oracles.len() as u8,
+            by: ctx.accounts.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Oracle quorum set to {} of {} for bets >= {}", 
+            quorum_size, registry.oracles.len(), quorum_bet_threshold);
+        Ok(())
+    }
+
+    /// Votar el resultado de una partida con la firma de un oráculo
+    /// Los votos se acumulan en una cuenta PendingResult; cuando M oráculos
+    /// registrados coinciden en el mismo resultado se liquida la partida y se cierra
+    /// PendingResult, devolviendo su renta al relayer del primer voto
+    pub fn submit_oracle_vote(
+        ctx: Context<SubmitOracleVote>,
+        winner_key: Pubkey,
+        is_draw: bool,
+        expiry: i64,
+        oracle: Pubkey,
+        record: Option<GameRecord>,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let registry = &ctx.accounts.oracle_registry;
+        let pending = &mut ctx.accounts.pending_result;
+        
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(game.mode == GameMode::Standard, ErrorCode::TrustlessGame);
+        require!(registry.oracles.contains(&oracle), ErrorCode::OracleNotRegistered);
+        require!(
+            is_draw || winner_key == game.player1 || winner_key == game.player2,
+            ErrorCode::InvalidWinner
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(current_time <= expiry, ErrorCode::ResultExpired);
+        
+        // Verificar firma Ed25519 del oráculo
+        let message = oracle_result_message(game.game_id, winner_key, is_draw, expiry, &record);
+        verify_ed25519_signature(&ctx.accounts.instructions_sysvar, &oracle, &message)?;
+        
+        if pending.votes.is_empty() {
+            pending.game_id = game.game_id;
+            pending.created_at = current_time;
+            pending.payer = ctx.accounts.relayer.key();
+        }
+        require!(
+            !pending.votes.iter().any(|v| v.oracle == oracle),
+            ErrorCode::OracleAlreadyVoted
+        );
+        require!(pending.votes.len() < MAX_ORACLES, ErrorCode::OracleRegistryFull);
+        
+        let vote = OracleVote {
+            oracle,
+            winner: if is_draw { None } else { Some(winner_key) },
+            is_draw,
+            record,
+        };
+        
+        if pending.votes.iter().any(|v| !v.agrees_with(&vote)) {
+            emit!(OracleDisagreement {
+                game_id: game.game_id,
+                oracle,
+                winner: vote.winner,
+                is_draw,
+                total_votes: pending.votes.len() as u8 + 1,
+                timestamp: current_time,
+            });
+        }
+        pending.votes.push(vote);
+        
+        // Solo cuentan los votos de oráculos que siguen registrados
+        let matching_votes = pending.votes.iter()
+            .filter(|v| v.agrees_with(&vote))
+            .filter(|v| registry.oracles.contains(&v.oracle))
+            .count();
+        
+        emit!(OracleVoteSubmitted {
+            game_id: game.game_id,
+            oracle,
+            winner: vote.winner,
+            is_draw,
+            matching_votes: matching_votes as u8,
+            quorum_size: registry.quorum_size,
+            timestamp: current_time,
+        });
+        
+        if matching_votes < registry.quorum_size as usize {
+            msg!("Game {}: oracle vote {}/{} recorded", 
+                game.game_id, matching_votes, registry.quorum_size);
+            return Ok(());
+        }
+        
+        // Quórum alcanzado: liquidar y devolver la renta de los votos a quien la pagó
+        let pending_payer = ctx.accounts.pending_payer.as_ref()
+            .filter(|payer| payer.key() == pending.payer)
+            .ok_or(ErrorCode::InvalidPendingPayer)?;
+        pending.close(pending_payer.to_account_info())?;
+        game.record = record;
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        let (prize_amount, dao_fee, fee_basis_points, fee_distribution) = settle_game(game, &payout, &ctx.accounts.token_state, winner_key, is_draw, current_time)?;
+        
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: game.winner,
+            is_draw,
+            prize_amount,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            record: game.record,
+            timestamp: current_time,
+        });
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        msg!("Game {} resolved by oracle quorum ({} votes). Winner: {:?}, Prize: {}, Fee: {}", 
+            game.game_id, matching_votes, game.winner, prize_amount, dao_fee);
+        Ok(())
+    }
+
+    // ============= OPTIMISTIC SETTLEMENT MODULE =============
+    // Liquidación optimista con ventana de disputa y árbitros
+
+    /// Crear la configuración de partidas (ventana de disputa, fianza y árbitros)
+    /// Solo authority puede ejecutar
+    pub fn initialize_game_config(
+        ctx: Context<InitializeGameConfig>,
+        challenge_window_seconds: i64,
+        dispute_bond: u64,
+        arbiters: Vec<Pubkey>,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let config = &mut ctx.accounts.game_config;
+        
+        require!(ctx.accounts.authority.key() == state.authority, ErrorCode::Unauthorized);
+        require!(challenge_window_seconds > 0, ErrorCode::InvalidChallengeWindow);
+        require!(arbiters.len() <= MAX_ARBITERS, ErrorCode::TooManyArbiters);
+        
+        config.challenge_window_seconds = challenge_window_seconds;
+        config.dispute_bond = dispute_bond;
+        config.arbiters = arbiters;
+        config.abandonment_min_seconds = DEFAULT_ABANDONMENT_MIN_SECONDS;
+        config.abandonment_percent = DEFAULT_ABANDONMENT_PERCENT;
+        config.abort_window_seconds = DEFAULT_ABORT_WINDOW_SECONDS;
+        config.expiry_bounty_lamports = 0;
+        config.optimistic_settlement = true;
+        config.dispute_timeout_seconds = DEFAULT_DISPUTE_TIMEOUT_SECONDS;
+        
+        emit!(GameConfigUpdated {
+            challenge_window_seconds,
+            dispute_bond,
+            arbiters: config.arbiters.clone(),
+            abandonment_min_seconds: config.abandonment_min_seconds,
+            abandonment_percent: config.abandonment_percent,
+            abort_window_seconds: config.abort_window_seconds,
+            expiry_bounty_lamports: config.expiry_bounty_lamports,
+            optimistic_settlement: config.optimistic_settlement,
+            dispute_timeout_seconds: config.dispute_timeout_seconds,
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Game config initialized. Window: {}s, Bond: {}", challenge_window_seconds, dispute_bond);
+        Ok(())
+    }
+
+    /// Actualizar la configuración de partidas
+    pub fn update_game_config(
+        ctx: Context<UpdateGameConfig>,
+        new_challenge_window_seconds: Option<i64>,
+        new_dispute_bond: Option<u64>,
+        new_arbiters: Option<Vec<Pubkey>>,
+        new_abandonment_min_seconds: Option<i64>,
+        new_abandonment_percent: Option<u16>,
+        new_abort_window_seconds: Option<i64>,
+        new_expiry_bounty_lamports: Option<u64>,
+        new_optimistic_settlement: Option<bool>,
+        new_dispute_timeout_seconds: Option<i64>,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let config = &mut ctx.accounts.game_config;
+        
+        require!(ctx.accounts.authority.key() == state.authority, ErrorCode::Unauthorized);
+        
+        if let Some(window) = new_challenge_window_seconds {
+            require!(window > 0, ErrorCode::InvalidChallengeWindow);
+            config.challenge_window_seconds = window;
+        }
+        
+        if let Some(bond) = new_dispute_bond {
+            config.dispute_bond = bond;
+        }
+        
+        if let Some(arbiters) = new_arbiters {
+            require!(arbiters.len() <= MAX_ARBITERS, ErrorCode::TooManyArbiters);
+            config.arbiters = arbiters;
+        }
+        
+        if let Some(min_seconds) = new_abandonment_min_seconds {
+            require!(min_seconds > 0, ErrorCode::InvalidAbandonmentThreshold);
+            config.abandonment_min_seconds = min_seconds;
+        }
+        
+        if let Some(percent) = new_abandonment_percent {
+            require!(percent > 0 && percent <= 200, ErrorCode::InvalidAbandonmentThreshold);
+            config.abandonment_percent = percent;
+        }
+        
+        if let Some(window) = new_abort_window_seconds {
+            require!(window >= 0, ErrorCode::InvalidAbortWindow);
+            config.abort_window_seconds = window;
+        }
+        
+        if let Some(bounty) = new_expiry_bounty_lamports {
+            config.expiry_bounty_lamports = bounty;
+        }
+        
+        if let Some(enabled) = new_optimistic_settlement {
+            config.optimistic_settlement = enabled;
+        }
+        
+        if let Some(timeout) = new_dispute_timeout_seconds {
+            require!(timeout > 0, ErrorCode::InvalidDisputeTimeout);
+            config.dispute_timeout_seconds = timeout;
+        }
+        
+        emit!(GameConfigUpdated {
+            challenge_window_seconds: config.challenge_window_seconds,
+            dispute_bond: config.dispute_bond,
+            arbiters: config.arbiters.clone(),
+            abandonment_min_seconds: config.abandonment_min_seconds,
+            abandonment_percent: config.abandonment_percent,
+            abort_window_seconds: config.abort_window_seconds,
+            expiry_bounty_lamports: config.expiry_bounty_lamports,
+            optimistic_settlement: config.optimistic_settlement,
+            dispute_timeout_seconds: config.dispute_timeout_seconds,
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Proponer resultado de una partida activa
+    /// Los fondos no se mueven hasta que termine la ventana de disputa
+    pub fn propose_result(
+        ctx: Context<ProposeResult>,
+        winner_key: Pubkey,
+        is_draw: bool,
+        record: Option<GameRecord>,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let state = &ctx.accounts.token_state;
+        let config = &ctx.accounts.game_config;
+        
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(game.mode == GameMode::Standard, ErrorCode::TrustlessGame);
+        require!(
+            ctx.accounts.authority.key() == state.authority,
+            ErrorCode::Unauthorized
+        );
+        require!(
+            is_draw || winner_key == game.player1 || winner_key == game.player2,
+            ErrorCode::InvalidWinner
+        );
+        // Las apuestas grandes solo se liquidan con el quórum de oráculos
+        require!(
+            !requires_oracle_quorum(&ctx.accounts.oracle_registry, game),
+            ErrorCode::QuorumRequired
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        let proposed = ResultClaim {
+            winner: if is_draw { None } else { Some(winner_key) },
+            is_draw,
+        };
+        
+        game.proposed_result = Some(proposed);
+        game.record = record;
+        game.settlement_deadline = current_time
+            .checked_add(config.challenge_window_seconds).unwrap();
+        game.status = GameStatus::PendingSettlement;
+        
+        emit!(ResultProposed {
+            game_id: game.game_id,
+            winner: proposed.winner,
+            is_draw,
+            record,
+            settlement_deadline: game.settlement_deadline,
+            timestamp: current_time,
+        });
+        
+        msg!("Game {}: result proposed, settles at {}", game.game_id, game.settlement_deadline);
+        Ok(())
+    }
+
+    /// Finalizar una liquidación propuesta sin disputa
+    /// Cualquiera puede ejecutarla cuando termina la ventana
+    pub fn finalize_settlement(ctx: Context<FinalizeSettlement>) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        
+        require!(
+            game.status == GameStatus::PendingSettlement,
+            ErrorCode::GameNotPendingSettlement
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(
+            current_time > game.settlement_deadline,
+            ErrorCode::ChallengeWindowOpen
+        );
+        
+        let proposed = game.proposed_result.ok_or(ErrorCode::GameNotPendingSettlement)?;
+        let winner_key = proposed.winner.unwrap_or_default();
+        
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        let (prize_amount, dao_fee, fee_basis_points, fee_distribution) = settle_game(game, &payout, &ctx.accounts.token_state, winner_key, proposed.is_draw, current_time)?;
+        
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: game.winner,
+            is_draw: proposed.is_draw,
+            prize_amount,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            record: game.record,
+            timestamp: current_time,
+        });
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        msg!("Game {} settled optimistically. Winner: {:?}, Prize: {}, Fee: {}", 
+            game.game_id, game.winner, prize_amount, dao_fee);
+        Ok(())
+    }
+
+    /// Disputar un resultado propuesto durante la ventana
+    /// El jugador deposita la fianza en el escrow de la partida
+    pub fn open_dispute(ctx: Context<OpenDispute>) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let config = &ctx.accounts.game_config;
+        let player = ctx.accounts.player.key();
+        
+        require!(
+            game.status == GameStatus::PendingSettlement,
+            ErrorCode::GameNotPendingSettlement
+        );
+        require!(
+            player == game.player1 || player == game.player2,
+            ErrorCode::NotAPlayer
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(
+            current_time <= game.settlement_deadline,
+            ErrorCode::ChallengeWindowClosed
+        );
+        
+        // Depositar fianza en el escrow
+        let escrow_before = ctx.accounts.escrow_token_account.amount;
+        if config.dispute_bond > 0 {
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.player_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.escrow_token_account.to_account_info(),
+                authority: ctx.accounts.player.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+            token_interface::transfer_checked(cpi_ctx, config.dispute_bond, ctx.accounts.mint.decimals)?;
+        }
+        
+        game.status = GameStatus::Disputed;
+        game.disputed_by = Some(player);
+        ctx.accounts.escrow_token_account.reload()?;
+        game.dispute_bond = ctx.accounts.escrow_token_account.amount
+            .checked_sub(escrow_before).unwrap();
+        // En disputa, settlement_deadline pasa a ser el plazo del árbitro
+        game.settlement_deadline = current_time
+            .checked_add(config.dispute_timeout_seconds).unwrap();
+        
+        emit!(DisputeOpened {
+            game_id: game.game_id,
+            disputed_by: player,
+            bond: config.dispute_bond,
+            ruling_deadline: game.settlement_deadline,
+            timestamp: current_time,
+        });
+        
+        msg!("Game {}: result disputed by {} with bond {}", game.game_id, player, config.dispute_bond);
+        Ok(())
+    }
+
+    /// Resolver una disputa
+    /// Solo un árbitro configurado puede ejecutar. Si el árbitro cambia el resultado
+    /// propuesto se devuelve la fianza; si lo confirma, la fianza va al treasury
+    pub fn resolve_dispute(
+        ctx: Context<ResolveDispute>,
+        winner_key: Pubkey,
+        is_draw: bool,
+        record: Option<GameRecord>,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let config = &ctx.accounts.game_config;
+        let arbiter = ctx.accounts.arbiter.key();
+        
+        require!(game.status == GameStatus::Disputed, ErrorCode::GameNotDisputed);
+        require!(config.arbiters.contains(&arbiter), ErrorCode::NotAnArbiter);
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        let ruling = ResultClaim {
+            winner: if is_draw { None } else { Some(winner_key) },
+            is_draw,
+        };
+        let disputer = game.disputed_by.ok_or(ErrorCode::GameNotDisputed)?;
+        let bond = game.dispute_bond;
+        let dispute_upheld = game.proposed_result != Some(ruling);
+        
+        // El árbitro puede corregir el registro; si no aporta uno se mantiene el propuesto
+        if record.is_some() {
+            game.record = record;
+        }
+        
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        let (prize_amount, dao_fee, fee_basis_points, fee_distribution) = settle_game(game, &payout, &ctx.accounts.token_state, winner_key, is_draw, current_time)?;
+        
+        // Fianza: devolver al jugador si tenía razón, si no va al treasury
+        if bond > 0 {
+            let bond_destination = if !dispute_upheld {
+                ctx.accounts.fees.treasury_token_account.to_account_info()
+            } else if disputer == game.player1 {
+                ctx.accounts.player1_token_account.to_account_info()
+            } else {
+                ctx.accounts.player2_token_account.to_account_info()
+            };
+            transfer_from_escrow(&payout, game, bond_destination, bond)?;
+        }
+        game.dispute_bond = 0;
+        
+        emit!(DisputeResolved {
+            game_id: game.game_id,
+            arbiter,
+            disputed_by: disputer,
+            dispute_upheld,
+            bond,
+            timestamp: current_time,
+        });
+        
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: game.winner,
+            is_draw,
+            prize_amount,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            record: game.record,
+            timestamp: current_time,
+        });
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        msg!("Game {}: dispute resolved by arbiter {}. Upheld: {}, Winner: {:?}", 
+            game.game_id, arbiter, dispute_upheld, game.winner);
+        Ok(())
+    }
+
+    /// Reembolsar una disputa que ningún árbitro resolvió a tiempo
+    /// Cualquiera puede ejecutarlo pasado el plazo: cada jugador recupera su depósito
+    /// y el que disputó su fianza, sin fee
+    pub fn expire_dispute(ctx: Context<ExpireDispute>) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        
+        require!(game.status == GameStatus::Disputed, ErrorCode::GameNotDisputed);
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(
+            current_time > game.settlement_deadline,
+            ErrorCode::DisputeNotExpired
+        );
+        
+        let disputer = game.disputed_by.ok_or(ErrorCode::GameNotDisputed)?;
+        let bond = game.dispute_bond;
+        let player1_refund = game.player1_deposit;
+        let player2_refund = game.player2_deposit;
+        let (player1_total, player2_total) = if disputer == game.player1 {
+            (player1_refund + bond, player2_refund)
+        } else {
+            (player1_refund, player2_refund + bond)
+        };
+        
+        if player1_total > 0 {
+            transfer_from_escrow_account(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &ctx.accounts.mint,
+                game,
+                ctx.accounts.player1_token_account.to_account_info(),
+                player1_total,
+            )?;
+        }
+        if player2_total > 0 {
+            transfer_from_escrow_account(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &ctx.accounts.mint,
+                game,
+                ctx.accounts.player2_token_account.to_account_info(),
+                player2_total,
+            )?;
+        }
+        
+        game.status = GameStatus::Cancelled;
+        game.winner = None;
+        game.dispute_bond = 0;
+        game.ended_at = current_time;
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        emit!(DisputeExpired {
+            game_id: game.game_id,
+            disputed_by: disputer,
+            player1_refund,
+            player2_refund,
+            bond,
+            timestamp: current_time,
+        });
+        
+        msg!("Game {}: dispute expired without ruling. Refunded {} / {} and bond {}", 
+            game.game_id, player1_refund, player2_refund, bond);
+        Ok(())
+    }
+
+    /// Presentar una prueba de fraude contra un resultado propuesto
+    /// Las hojas aportadas deben estar en el registro comprometido:
+    /// - IllegalMove: la jugada es ilegal; el resultado se anula y gana el otro bando
+    /// - WrongStartPosition: la hoja 0 no parte de la posición inicial
+    /// - BrokenChain: la jugada k no lleva a la posición de la hoja k+1
+    /// En los dos últimos casos el registro no describe una partida real:
+    /// se anula el resultado y se devuelven los depósitos sin fee
+    pub fn submit_fraud_proof(ctx: Context<SubmitFraudProof>, fraud: FraudProof) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let prover = ctx.accounts.player.key();
+        
+        require!(
+            game.status == GameStatus::PendingSettlement || game.status == GameStatus::Disputed,
+            ErrorCode::GameNotPendingSettlement
+        );
+        require!(
+            prover == game.player1 || prover == game.player2,
+            ErrorCode::NotAPlayer
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        if game.status == GameStatus::PendingSettlement {
+            require!(
+                current_time <= game.settlement_deadline,
+                ErrorCode::ChallengeWindowClosed
+            );
+        }
+        
+        // Verificar inclusión de las hojas en el registro comprometido y la infracción
+        let record = game.record.ok_or(ErrorCode::NoGameRecord)?;
+        let illegal_move = match &fraud {
+            FraudProof::IllegalMove(leaf) => {
+                let (position, mv) = verify_move_leaf(&record, leaf)?;
+                require!(!position.is_legal(mv), ErrorCode::MoveIsLegal);
+                Some((leaf.ply, position.white_to_move))
+            }
+            FraudProof::WrongStartPosition(leaf) => {
+                require!(leaf.ply == 0, ErrorCode::InvalidMoveProof);
+                let (position, _) = verify_move_leaf(&record, leaf)?;
+                require!(position != Position::initial(), ErrorCode::RecordIsConsistent);
+                None
+            }
+            FraudProof::BrokenChain(leaf, next_leaf) => {
+                require!(leaf.ply.checked_add(1) == Some(next_leaf.ply), ErrorCode::InvalidMoveProof);
+                let (mut position, mv) = verify_move_leaf(&record, leaf)?;
+                let (next_position, _) = verify_move_leaf(&record, next_leaf)?;
+                // Una jugada ilegal se prueba con IllegalMove
+                require!(position.play(mv), ErrorCode::InvalidMoveProof);
+                require!(position != next_position, ErrorCode::RecordIsConsistent);
+                None
+            }
+        };
+        
+        let disputer = game.disputed_by;
+        let bond = game.dispute_bond;
+        
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        
+        // El resultado propuesto era incorrecto: devolver la fianza de una disputa abierta
+        let bond_destination = match disputer {
+            Some(disputer) if disputer == game.player1 => Some(ctx.accounts.player1_token_account.to_account_info()),
+            Some(_) => Some(ctx.accounts.player2_token_account.to_account_info()),
+            None => None,
+        };
+        
+        match illegal_move {
+            Some((ply, white_to_move)) => {
+                // El infractor es el bando que movía; gana su oponente
+                let (offender, winner_key) = if white_to_move {
+                    (game.player1, game.player2)
+                } else {
+                    (game.player2, game.player1)
+                };
+                
+                let (prize_amount, dao_fee, fee_basis_points, fee_distribution) = settle_game(game, &payout, &ctx.accounts.token_state, winner_key, false, current_time)?;
+                
+                if let Some(destination) = bond_destination {
+                    if bond > 0 {
+                        transfer_from_escrow(&payout, game, destination, bond)?;
+                    }
+                    game.dispute_bond = 0;
+                }
+                
+                emit!(FraudProven {
+                    game_id: game.game_id,
+                    ply,
+                    offender,
+                    prover,
+                    timestamp: current_time,
+                });
+                
+                emit!(GameResolved {
+                    game_id: game.game_id,
+                    winner: game.winner,
+                    is_draw: false,
+                    prize_amount,
+                    dao_fee,
+                    fee_basis_points,
+                    fee_distribution,
+                    record: game.record,
+                    timestamp: current_time,
+                });
+                
+                msg!("Game {}: illegal move {} by {} proven, result overturned. Winner: {}", 
+                    game.game_id, ply, offender, winner_key);
+            }
+            None => {
+                // Registro inválido: anular y devolver lo depositado
+                let player1_refund = game.player1_deposit;
+                let player2_refund = game.player2_deposit;
+                if player1_refund > 0 {
+                    transfer_from_escrow(&payout, game, ctx.accounts.player1_token_account.to_account_info(), player1_refund)?;
+                }
+                if player2_refund > 0 {
+                    transfer_from_escrow(&payout, game, ctx.accounts.player2_token_account.to_account_info(), player2_refund)?;
+                }
+                if let Some(destination) = bond_destination {
+                    if bond > 0 {
+                        transfer_from_escrow(&payout, game, destination, bond)?;
+                    }
+                    game.dispute_bond = 0;
+                }
+                
+                game.status = GameStatus::Cancelled;
+                game.winner = None;
+                game.ended_at = current_time;
+                
+                emit!(InvalidRecordProven {
+                    game_id: game.game_id,
+                    prover,
+                    player1_refund,
+                    player2_refund,
+                    timestamp: current_time,
+                });
+                
+                msg!("Game {}: committed record proven inconsistent, result voided. Refunded {} / {}", 
+                    game.game_id, player1_refund, player2_refund);
+            }
+        }
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        Ok(())
+    }
+
+    // ============= TRUSTLESS GAME MODULE =============
+    // Partidas validadas on-chain: player1 juega con blancas, player2 con negras
+
+    /// Crear el tablero de una partida trustless en la posición inicial
+    /// Solo player1 puede ejecutar, antes de que se juegue ninguna jugada
+    pub fn init_board(ctx: Context<InitBoard>) -> Result<()> {
+        let game = &ctx.accounts.game_match;
+        let board = &mut ctx.accounts.chess_board;
+        
+        require!(game.mode == GameMode::Trustless, ErrorCode::NotTrustlessGame);
+        require!(game.player1 == ctx.accounts.player1.key(), ErrorCode::Unauthorized);
+        require!(
+            game.status == GameStatus::Waiting || game.status == GameStatus::Active,
+            ErrorCode::GameNotActive
+        );
+        
+        board.game_id = game.game_id;
+        board.ply_count = 0;
+        board.position_history = Vec::new();
+        store_position(board, &Position::initial());
+        board.position_history.push(position_hash(&Position::initial()));
+        
+        msg!("Board initialized for trustless game {}", game.game_id);
+        Ok(())
+    }
+
+    /// Jugar una jugada en una partida trustless
+    /// La jugada se valida on-chain; si la posición resultante es terminal
+    /// (mate, ahogado, triple repetición, 50 movimientos o material insuficiente)
+    /// el escrow se liquida automáticamente
+    pub fn submit_move(
+        ctx: Context<SubmitMove>,
+        from: u8,
+        to: u8,
+        promotion: u8,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let board = &mut ctx.accounts.chess_board;
+        let player = ctx.accounts.player.key();
+        
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(game.mode == GameMode::Trustless, ErrorCode::NotTrustlessGame);
+        
+        // Blancas = player1, negras = player2
+        let side_to_move = if board.white_to_move { game.player1 } else { game.player2 };
+        require!(player == side_to_move, ErrorCode::NotYourTurn);
+        
+        let mut position = load_position(board);
+        let mv = ChessMove { from, to, promotion };
+        require!(position.play(mv), ErrorCode::IllegalMove);
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        press_game_clock(game, current_time)?;
+        record_activity(game, player, current_time);
+        
+        // El historial solo necesita posiciones desde la última jugada irreversible
+        if position.halfmove_clock == 0 {
+            board.position_history.clear();
+        }
+        let hash = position_hash(&position);
+        board.position_history.push(hash);
+        let repetitions = board.position_history.iter().filter(|&&h| h == hash).count();
+        
+        store_position(board, &position);
+        board.ply_count = board.ply_count.checked_add(1).unwrap();
+        
+        let status = position.status();
+        
+        emit!(MovePlayed {
+            game_id: game.game_id,
+            player,
+            from,
+            to,
+            promotion,
+            ply: board.ply_count,
+            in_check: position.in_check(),
+            timestamp: current_time,
+        });
+        
+        let is_draw = match status {
+            PositionStatus::Checkmate => false,
+            PositionStatus::Ongoing if repetitions < 3 => {
+                msg!("Game {}: move {} played", game.game_id, board.ply_count);
+                return Ok(());
+            }
+            _ => true,
+        };
+        
+        // Posición terminal: liquidar sin authority
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        let (prize_amount, dao_fee, fee_basis_points, fee_distribution) = settle_game(game, &payout, &ctx.accounts.token_state, player, is_draw, current_time)?;
+        
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: game.winner,
+            is_draw,
+            prize_amount,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            record: game.record,
+            timestamp: current_time,
+        });
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        msg!("Trustless game {} finished on move {}. Winner: {:?}, Prize: {}, Fee: {}", 
+            game.game_id, board.ply_count, game.winner, prize_amount, dao_fee);
+        Ok(())
+    }
+
+    /// Probar on-chain que una jugada forma parte del registro comprometido
+    /// de una partida liquidada (auditoría o disputa)
+    pub fn prove_move(
+        ctx: Context<ProveMove>,
+        ply: u16,
+        position_before: Vec<u8>,
+        from: u8,
+        to: u8,
+        promotion: u8,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let game = &ctx.accounts.game_match;
+        let record = game.record.ok_or(ErrorCode::NoGameRecord)?;
+        
+        let (position, mv) = verify_move_leaf(&record, &MoveLeafProof {
+            ply,
+            position_before,
+            from,
+            to,
+            promotion,
+            proof,
+        })?;
+        
+        emit!(MoveProven {
+            game_id: game.game_id,
+            ply,
+            from,
+            to,
+            promotion,
+            is_legal: position.is_legal(mv),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Game {}: move {} proven against committed record", game.game_id, ply);
+        Ok(())
+    }
+
+    // ============= CHESS CLOCK MODULE =============
+
+    /// Pulsar el reloj tras una jugada (partidas estándar)
+    /// Solo el jugador al que le toca mover puede pulsarlo
+    pub fn press_clock(ctx: Context<PressClock>) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let player = ctx.accounts.player.key();
+        
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(game.mode == GameMode::Standard, ErrorCode::TrustlessGame);
+        
+        let side_to_move = if game.player1_to_move { game.player1 } else { game.player2 };
+        require!(player == side_to_move, ErrorCode::NotYourTurn);
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        press_game_clock(game, current_time)?;
+        record_activity(game, player, current_time);
+        
+        emit!(ClockPressed {
+            game_id: game.game_id,
+            player,
+            player1_time_left: game.player1_time_left,
+            player2_time_left: game.player2_time_left,
+            timestamp: current_time,
+        });
+        
+        Ok(())
+    }
+
+    /// Señal de actividad de un jugador (evita reclamos por abandono)
+    pub fn heartbeat(ctx: Context<Heartbeat>) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let player = ctx.accounts.player.key();
+        
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            player == game.player1 || player == game.player2,
+            ErrorCode::NotAPlayer
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        record_activity(game, player, current_time);
+        
+        emit!(PlayerActivity {
+            game_id: game.game_id,
+            player,
+            reported_by: player,
+            timestamp: current_time,
+        });
+        
+        Ok(())
+    }
+
+    /// Registrar actividad de un jugador observada por el backend
+    /// Solo authority puede ejecutar
+    pub fn record_player_activity(ctx: Context<RecordPlayerActivity>, player: Pubkey) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let state = &ctx.accounts.token_state;
+        
+        require!(ctx.accounts.authority.key() == state.authority, ErrorCode::Unauthorized);
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            player == game.player1 || player == game.player2,
+            ErrorCode::NotAPlayer
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        record_activity(game, player, current_time);
+        
+        emit!(PlayerActivity {
+            game_id: game.game_id,
+            player,
+            reported_by: ctx.accounts.authority.key(),
+            timestamp: current_time,
+        });
+        
+        Ok(())
+    }
+
+    /// Reclamar victoria por caída de bandera
+    /// Solo el jugador cuyo oponente se quedó sin tiempo en su turno puede reclamar
+    pub fn claim_flag(ctx: Context<ClaimFlag>) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let claimer = ctx.accounts.claimer.key();
+        
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            claimer == game.player1 || claimer == game.player2,
+            ErrorCode::NotAPlayer
+        );
+        
+        // El reloj que corre es el del oponente del reclamante
+        let side_to_move = if game.player1_to_move { game.player1 } else { game.player2 };
+        require!(claimer != side_to_move, ErrorCode::NotYourTurn);
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(has_flag_fallen(game, current_time), ErrorCode::FlagNotFallen);
+        
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        let (prize_amount, dao_fee, fee_basis_points, fee_distribution) = settle_game(game, &payout, &ctx.accounts.token_state, claimer, false, current_time)?;
+        game.status = GameStatus::Timeout;
+        if game.player1_to_move {
+            game.player1_time_left = 0;
+        } else {
+            game.player2_time_left = 0;
+        }
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        emit!(GameTimeout {
+            game_id: game.game_id,
+            claimer,
+            prize_amount,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            timestamp: current_time,
+        });
+        
+        msg!("Game {}: flag fell, {} wins. Prize: {}", game.game_id, claimer, prize_amount);
+        Ok(())
+    }
+
+    // ============= PLAYER ENDINGS MODULE =============
+    // Abandono y tablas acordadas firmadas por los propios jugadores
+
+    /// Rendirse: el oponente recibe el premio inmediatamente
+    pub fn resign(ctx: Context<PlayerEnding>) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let player = ctx.accounts.player.key();
+        
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            player == game.player1 || player == game.player2,
+            ErrorCode::NotAPlayer
+        );
+        
+        let winner_key = if player == game.player1 { game.player2 } else { game.player1 };
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        let (prize_amount, dao_fee, fee_basis_points, fee_distribution) = settle_game(game, &payout, &ctx.accounts.token_state, winner_key, false, current_time)?;
+        game.draw_offered_by = None;
+        
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: game.winner,
+            is_draw: false,
+            prize_amount,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            record: game.record,
+            timestamp: current_time,
+        });
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        msg!("Game {}: {} resigned. Winner: {}, Prize: {}", 
+            game.game_id, player, winner_key, prize_amount);
+        Ok(())
+    }
+
+    /// Ofrecer tablas al oponente
+    /// La oferta queda rechazada si el oponente pulsa su reloj sin aceptarla
+    pub fn offer_draw(ctx: Context<OfferDraw>) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let player = ctx.accounts.player.key();
+        
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            player == game.player1 || player == game.player2,
+            ErrorCode::NotAPlayer
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        game.draw_offered_by = Some(player);
+        record_activity(game, player, current_time);
+        
+        emit!(DrawOffered {
+            game_id: game.game_id,
+            offered_by: player,
+            timestamp: current_time,
+        });
+        
+        msg!("Game {}: draw offered by {}", game.game_id, player);
+        Ok(())
+    }
+
+    /// Aceptar la oferta de tablas del oponente: el prize pool se reparte según lo depositado
+    pub fn accept_draw(ctx: Context<PlayerEnding>) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let player = ctx.accounts.player.key();
+        
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            player == game.player1 || player == game.player2,
+            ErrorCode::NotAPlayer
+        );
+        
+        let opponent = if player == game.player1 { game.player2 } else { game.player1 };
+        require!(game.draw_offered_by == Some(opponent), ErrorCode::NoDrawOffer);
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        let (prize_amount, dao_fee, fee_basis_points, fee_distribution) = settle_game(game, &payout, &ctx.accounts.token_state, Pubkey::default(), true, current_time)?;
+        game.draw_offered_by = None;
+        
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: None,
+            is_draw: true,
+            prize_amount,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            record: game.record,
+            timestamp: current_time,
+        });
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        msg!("Game {}: draw agreed. Prize split: {}, Fee: {}", 
+            game.game_id, prize_amount, dao_fee);
+        Ok(())
+    }
+
+    /// Abortar una partida recién empezada
+    /// Cualquier jugador puede abortar dentro de la ventana inicial y antes de que
+    /// ambos hayan movido; ambas apuestas se devuelven sin fee del DAO
+    pub fn abort_game(ctx: Context<AbortGame>) -> Result<()> {
+        let game = &mut ctx.accounts.game_match;
+        let config = &ctx.accounts.game_config;
+        let player = ctx.accounts.player.key();
+        
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            player == game.player1 || player == game.player2,
+            ErrorCode::NotAPlayer
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        let within_window = current_time - game.started_at <= config.abort_window_seconds;
+        require!(
+            game.moves_played < 2 && within_window,
+            ErrorCode::AbortWindowClosed
+        );
+        
+        let payout = EscrowPayout {
+            escrow_token_account: &ctx.accounts.escrow_token_account,
+            mint: &ctx.accounts.mint,
+            player1_token_account: &ctx.accounts.player1_token_account,
+            player2_token_account: &ctx.accounts.player2_token_account,
+            fees: &ctx.accounts.fees,
+            token_program: &ctx.accounts.token_program,
+        };
+        
+        // Reembolso íntegro de lo que cada jugador depositó en el escrow
+        let player1_refund = game.player1_deposit;
+        let player2_refund = game.player2_deposit;
+        transfer_from_escrow(&payout, game, ctx.accounts.player1_token_account.to_account_info(), player1_refund)?;
+        transfer_from_escrow(&payout, game, ctx.accounts.player2_token_account.to_account_info(), player2_refund)?;
+        
+        game.status = GameStatus::Aborted;
+        game.winner = None;
+        game.ended_at = current_time;
+        
+        // Decrementar juegos activos
+        ctx.accounts.game_counter.active_games = ctx.accounts.game_counter
+            .active_games.saturating_sub(1);
+        
+        emit!(GameAborted {
+            game_id: game.game_id,
+            aborted_by: player,
+            player1_refund,
+            player2_refund,
+            moves_played: game.moves_played,
+            timestamp: current_time,
+        });
+        
+        msg!("Game {} aborted by {}. Refunded {} / {}", 
+            game.game_id, player, player1_refund, player2_refund);
+        Ok(())
+    }
+
+    /// Revancha de una partida terminada con los colores cambiados
+    /// Cada jugador la llama una vez para depositar su apuesta en el escrow nuevo;
+    /// la primera llamada crea la partida enlazada a la anterior y la segunda la arranca
+    pub fn rematch(ctx: Context<Rematch>, new_game_id: u64) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let previous = &mut ctx.accounts.previous_game;
+        let game = &mut ctx.accounts.game_match;
+        let player = ctx.accounts.player.key();
+        
+        require!(!state.is_paused, ErrorCode::ProgramPaused);
+        require!(
+            previous.status == GameStatus::Completed || previous.status == GameStatus::Draw,
+            ErrorCode::GameNotFinished
+        );
+        require!(
+            player == previous.player1 || player == previous.player2,
+            ErrorCode::NotAPlayer
+        );
+        // Las partidas de serie, arena o batalla se liquidan con su evento, no por revancha
+        require!(
+            previous.series_id.is_none()
+                && previous.arena_id.is_none()
+                && previous.team_battle_id.is_none(),
+            ErrorCode::RematchNotAllowed
+        );
+        require!(
+            !is_blacklisted(&ctx.accounts.blacklist, &player),
+            ErrorCode::AddressBlacklisted
+        );
+        // Los límites del mint pueden haber cambiado desde la partida original
+        let wager_mint = &ctx.accounts.wager_mint;
+        require!(
+            (wager_mint.min_stake..=wager_mint.max_stake).contains(&previous.bet_amount)
+                && (wager_mint.min_stake..=wager_mint.max_stake).contains(&previous.player2_stake),
+            ErrorCode::StakeOutOfRange
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        match previous.rematch_game_id {
+            None => {
+                // Primera llamada: crear la partida con los colores cambiados
+                require!(game.player1 == Pubkey::default(), ErrorCode::GameIdTaken);
+                previous.rematch_game_id = Some(new_game_id);
+                
+                game.game_id = new_game_id;
+                game.player1 = previous.player2;
+                game.player2 = previous.player1;
+                game.bet_amount = previous.player2_stake;
+                game.player2_stake = previous.bet_amount;
+                game.time_control = previous.time_control;
+                game.title = previous.title.clone();
+                game.status = GameStatus::Waiting;
+                game.mode = previous.mode;
+                game.created_at = current_time;
+                game.player1_time_left = previous.time_control.base_seconds as i64;
+                game.player2_time_left = previous.time_control.base_seconds as i64;
+                game.player1_to_move = true;
+                game.invited_opponent = Some(previous.player1);
+                game.expires_at = current_time.checked_add(DEFAULT_JOIN_WINDOW_SECONDS).unwrap();
+                game.referrer = previous.referrer;
+                game.mint = previous.mint;
+                game.escrow_bump = ctx.bumps.escrow_token_account;
+                game.previous_game = Some(previous.game_id);
+                game.rent_payer = player;
+                
+                ctx.accounts.game_counter.total_games += 1;
+                ctx.accounts.game_counter.active_games += 1;
+                
+                emit!(RematchCreated {
+                    game_id: new_game_id,
+                    previous_game: previous.game_id,
+                    white: game.player1,
+                    black: game.player2,
+                    timestamp: current_time,
+                });
+            }
+            Some(rematch_id) => {
+                require!(rematch_id == new_game_id, ErrorCode::RematchAlreadyCreated);
+            }
+        }
+        
+        require!(game.status == GameStatus::Waiting, ErrorCode::GameNotWaiting);
+        require!(current_time <= game.expires_at, ErrorCode::GameExpired);
+        
+        let (already_deposited, stake) = if player == game.player1 {
+            (game.player1_deposit > 0, game.bet_amount)
+        } else {
+            (game.player2_deposit > 0, game.player2_stake)
+        };
+        require!(!already_deposited, ErrorCode::AlreadyDeposited);
+        
+        // Depositar en el escrow nuevo y contar lo que realmente llegó
+        let escrow_before = ctx.accounts.escrow_token_account.amount;
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.player_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.escrow_token_account.to_account_info(),
+            authority: ctx.accounts.player.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token_interface::transfer_checked(cpi_ctx, stake, ctx.accounts.mint.decimals)?;
+        
+        ctx.accounts.escrow_token_account.reload()?;
+        let deposited = ctx.accounts.escrow_token_account.amount
+            .checked_sub(escrow_before).unwrap();
+        if player == game.player1 {
+            game.player1_deposit = deposited;
+        } else {
+            game.player2_deposit = deposited;
+        }
+        game.total_pot = game.player1_deposit.checked_add(game.player2_deposit).unwrap();
+        
+        // Con ambos depósitos la revancha arranca
+        if game.player1_deposit > 0 && game.player2_deposit > 0 {
+            game.status = GameStatus::Active;
+            game.started_at = current_time;
+            game.clock_running_since = current_time;
+            game.player1_last_active = current_time;
+            game.player2_last_active = current_time;
+            
+            emit!(GameJoined {
+                game_id: game.game_id,
+                player1: game.player1,
+                player2: game.player2,
+                total_pot: game.total_pot,
+                timestamp: current_time,
+            });
+        }
+        
+        msg!("Rematch {} of game {}: {} deposited {}", 
+            game.game_id, previous.game_id, player, deposited);
+        Ok(())
+    }
+
+    // ============= MATCH SERIES MODULE =============
+    // Series al mejor de N (o N partidas por puntos) con un único escrow
+
+    /// Crear una serie entre dos jugadores
+    /// El creador deposita su apuesta una sola vez para toda la serie.
+    /// BestOf termina en cuanto un jugador no puede ser alcanzado; FixedGames
+    /// juega siempre las N partidas y gana quien sume más puntos
+    pub fn create_series(
+        ctx: Context<CreateSeries>,
+        series_id: u64,
+        stake: u64,
+        format: SeriesFormat,
+        games_planned: u8,
+        time_control: TimeControl,
+        invited_opponent: Option<Pubkey>,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let series = &mut ctx.accounts.match_series;
+        
+        require!(!state.is_paused, ErrorCode::ProgramPaused);
+        require!(
+            stake >= ctx.accounts.wager_mint.min_stake && stake <= ctx.accounts.wager_mint.max_stake,
+            ErrorCode::StakeOutOfRange
+        );
+        require!(
+            games_planned > 0 && games_planned <= MAX_SERIES_GAMES,
+            ErrorCode::InvalidSeriesLength
+        );
+        require!(
+            format != SeriesFormat::BestOf || games_planned % 2 == 1,
+            ErrorCode::InvalidSeriesLength
+        );
+        require!(is_valid_time_control(&time_control), ErrorCode::InvalidTimeControl);
+        require!(
+            invited_opponent != Some(ctx.accounts.player1.key()),
+            ErrorCode::CannotJoinOwnGame
+        );
+        require!(
+            !is_blacklisted(&ctx.accounts.blacklist, &ctx.accounts.player1.key()),
+            ErrorCode::AddressBlacklisted
+        );
+        
+        // Depositar la apuesta de toda la serie
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.player1_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.escrow_token_account.to_account_info(),
+            authority: ctx.accounts.player1.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token_interface::transfer_checked(cpi_ctx, stake, ctx.accounts.mint.decimals)?;
+        
+        ctx.accounts.escrow_token_account.reload()?;
+        let deposited = ctx.accounts.escrow_token_account.amount;
+        
+        series.series_id = series_id;
+        series.player1 = ctx.accounts.player1.key();
+        series.player2 = Pubkey::default();
+        series.mint = ctx.accounts.mint.key();
+        series.stake = stake;
+        series.player1_deposit = deposited;
+        series.player2_deposit = 0;
+        series.total_pot = deposited;
+        series.format = format;
+        series.games_planned = games_planned;
+        series.games_played = 0;
+        series.player1_points = 0;
+        series.player2_points = 0;
+        series.time_control = time_control;
+        series.current_game_id = None;
+        series.status = GameStatus::Waiting;
+        series.winner = None;
+        series.invited_opponent = invited_opponent;
+        series.created_at = Clock::get()?.unix_timestamp;
+        series.started_at = 0;
+        series.ended_at = 0;
+        series.escrow_bump = ctx.bumps.escrow_token_account;
+        
+        emit!(SeriesCreated {
+            series_id,
+            player1: series.player1,
+            mint: series.mint,
+            stake,
+            format,
+            games_planned,
+            time_control,
+            invited_opponent,
+            timestamp: series.created_at,
+        });
+        
+        msg!("Series {} created: {:?} {} games, stake {}", series_id, format, games_planned, stake);
+        Ok(())
+    }
+
+    /// Unirse a una serie depositando la misma apuesta
+    pub fn join_series(ctx: Context<JoinSeries>) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let series = &mut ctx.accounts.match_series;
+        let player2 = ctx.accounts.player2.key();
+        
+        require!(!state.is_paused, ErrorCode::ProgramPaused);
+        require!(series.status == GameStatus::Waiting, ErrorCode::GameNotWaiting);
+        require!(series.player1 != player2, ErrorCode::CannotJoinOwnGame);
+        if let Some(invited) = series.invited_opponent {
+            require!(invited == player2, ErrorCode::NotInvited);
+        }
+        require!(
+            !is_blacklisted(&ctx.accounts.blacklist, &player2),
+            ErrorCode::AddressBlacklisted
+        );
+        
+        let escrow_before = ctx.accounts.escrow_token_account.amount;
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.player2_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.escrow_token_account.to_account_info(),
+            authority: ctx.accounts.player2.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token_interface::transfer_checked(cpi_ctx, series.stake, ctx.accounts.mint.decimals)?;
+        
+        ctx.accounts.escrow_token_account.reload()?;
+        let deposited = ctx.accounts.escrow_token_account.amount
+            .checked_sub(escrow_before).unwrap();
+        
+        series.player2 = player2;
+        series.player2_deposit = deposited;
+        series.total_pot = series.player1_deposit.checked_add(deposited).unwrap();
+        series.status = GameStatus::Active;
+        series.started_at = Clock::get()?.unix_timestamp;
+        
+        emit!(SeriesJoined {
+            series_id: series.series_id,
+            player1: series.player1,
+            player2,
+            total_pot: series.total_pot,
+            timestamp: series.started_at,
+        });
+        
+        msg!("Player {} joined series {}. Total pot: {}", player2, series.series_id, series.total_pot);
+        Ok(())
+    }
+
+    /// Cancelar una serie sin oponente y reembolsar a player1
+    pub fn cancel_series(ctx: Context<CancelSeries>) -> Result<()> {
+        let series = &mut ctx.accounts.match_series;
+        
+        require!(series.status == GameStatus::Waiting, ErrorCode::GameNotWaiting);
+        require!(series.player1 == ctx.accounts.player1.key(), ErrorCode::Unauthorized);
+        
+        let refund_amount = series.player1_deposit;
+        transfer_from_escrow_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.mint,
+            series,
+            ctx.accounts.player1_token_account.to_account_info(),
+            refund_amount,
+        )?;
+        
+        series.status = GameStatus::Cancelled;
+        series.ended_at = Clock::get()?.unix_timestamp;
+        
+        emit!(SeriesCancelled {
+            series_id: series.series_id,
+            player1: series.player1,
+            refund_amount,
+            timestamp: series.ended_at,
+        });
+        
+        msg!("Series {} cancelled. Refunded {} to player1", series.series_id, refund_amount);
+        Ok(())
+    }
+
+    /// Empezar la siguiente partida de la serie
+    /// Los colores se alternan: en las partidas impares player1 lleva blancas.
+    /// La partida no tiene escrow propio: se resuelve con resolve_series_game
+    pub fn start_series_game(ctx: Context<StartSeriesGame>, game_id: u64) -> Result<()> {
+        let series = &mut ctx.accounts.match_series;
+        let game = &mut ctx.accounts.game_match;
+        let player = ctx.accounts.player.key();
+        
+        require!(series.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            player == series.player1 || player == series.player2,
+            ErrorCode::NotAPlayer
+        );
+        require!(series.current_game_id.is_none(), ErrorCode::SeriesGameInProgress);
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        let (white, black) = if series.games_played % 2 == 1 {
+            (series.player2, series.player1)
+        } else {
+            (series.player1, series.player2)
+        };
+        
+        game.game_id = game_id;
+        game.player1 = white;
+        game.player2 = black;
+        game.time_control = series.time_control;
+        game.status = GameStatus::Active;
+        game.mode = GameMode::Standard;
+        game.created_at = current_time;
+        game.started_at = current_time;
+        game.player1_time_left = series.time_control.base_seconds as i64;
+        game.player2_time_left = series.time_control.base_seconds as i64;
+        game.player1_to_move = true;
+        game.clock_running_since = current_time;
+        game.player1_last_active = current_time;
+        game.player2_last_active = current_time;
+        game.mint = series.mint;
+        game.series_id = Some(series.series_id);
+        game.rent_payer = player;
+        
+        series.current_game_id = Some(game_id);
+        
+        emit!(SeriesGameStarted {
+            series_id: series.series_id,
+            game_id,
+            game_number: series.games_played + 1,
+            white,
+            black,
+            timestamp: current_time,
+        });
+        
+        msg!("Series {}: game {} started ({} vs {})", series.series_id, game_id, white, black);
+        Ok(())
+    }
+
+    /// Registrar el resultado de la partida en curso de una serie
+    /// Solo authority. Actualiza el marcador (victoria = 1 punto, tablas = ½) y,
+    /// si la serie queda decidida, paga el escrow de la serie
+    pub fn resolve_series_game(
+        ctx: Context<ResolveSeriesGame>,
+        winner_key: Pubkey,
+        is_draw: bool,
+        record: Option<GameRecord>,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let series = &mut ctx.accounts.match_series;
+        let game = &mut ctx.accounts.game_match;
+        
+        require!(
+            ctx.accounts.authority.key() == state.authority,
+            ErrorCode::Unauthorized
+        );
+        require!(series.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            series.current_game_id == Some(game.game_id),
+            ErrorCode::NotSeriesGame
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        // Resultado de la partida: sin movimiento de tokens
+        game.record = record;
+        game.ended_at = current_time;
+        if is_draw {
+            game.status = GameStatus::Draw;
+            game.winner = None;
+            series.player1_points += 1;
+            series.player2_points += 1;
+        } else {
+            require!(
+                winner_key == game.player1 || winner_key == game.player2,
+                ErrorCode::InvalidWinner
+            );
+            game.status = GameStatus::Completed;
+            game.winner = Some(winner_key);
+            if winner_key == series.player1 {
+                series.player1_points += 2;
+            } else {
+                series.player2_points += 2;
+            }
+        }
+        series.games_played += 1;
+        series.current_game_id = None;
+        
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: game.winner,
+            is_draw,
+            prize_amount: 0,
+            dao_fee: 0,
+            fee_basis_points: 0,
+            fee_distribution: FeeDistribution::default(),
+            record: game.record,
+            timestamp: current_time,
+        });
+        
+        emit!(SeriesScoreUpdated {
+            series_id: series.series_id,
+            game_id: game.game_id,
+            games_played: series.games_played,
+            player1_points: series.player1_points,
+            player2_points: series.player2_points,
+            timestamp: current_time,
+        });
+        
+        // ¿Serie decidida?
+        let Some(series_winner) = series_outcome(series) else {
+            msg!("Series {}: {} - {} (half-points)", 
+                series.series_id, series.player1_points, series.player2_points);
+            return Ok(());
+        };
+        
+        let series_is_draw = series_winner.is_none();
+        let fee_basis_points = game_fee_basis_points(state, series.stake, series_is_draw);
+        let dao_fee = series.total_pot
+            .checked_mul(fee_basis_points as u64).unwrap()
+            .checked_div(10000).unwrap();
+        let prize_pool = series.total_pot.checked_sub(dao_fee).unwrap();
+        
+        let (player1_prize, player2_prize) = match series_winner {
+            None => {
+                let total_deposits = series.player1_deposit as u128 + series.player2_deposit as u128;
+                let player1_share = (prize_pool as u128 * series.player1_deposit as u128
+                    / total_deposits.max(1)) as u64;
+                (player1_share, prize_pool - player1_share)
+            }
+            Some(winner) if winner == series.player1 => (prize_pool, 0),
+            Some(_) => (0, prize_pool),
+        };
+        
+        if player1_prize > 0 {
+            transfer_from_escrow_account(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &ctx.accounts.mint,
+                series,
+                ctx.accounts.player1_token_account.to_account_info(),
+                player1_prize,
+            )?;
+        }
+        if player2_prize > 0 {
+            transfer_from_escrow_account(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &ctx.accounts.mint,
+                series,
+                ctx.accounts.player2_token_account.to_account_info(),
+                player2_prize,
+            )?;
+        }
+        
+        let fee_distribution = distribute_escrow_fee(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.fees,
+            series,
+            &state.fee_split,
+            dao_fee,
+        )?;
+        
+        series.status = if series_is_draw { GameStatus::Draw } else { GameStatus::Completed };
+        series.winner = series_winner;
+        series.ended_at = current_time;
+        
+        emit!(SeriesCompleted {
+            series_id: series.series_id,
+            winner: series.winner,
+            is_draw: series_is_draw,
+            player1_points: series.player1_points,
+            player2_points: series.player2_points,
+            player1_prize,
+            player2_prize,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            timestamp: current_time,
+        });
+        
+        msg!("Series {} finished. Winner: {:?}, Fee: {}", series.series_id, series.winner, dao_fee);
+        Ok(())
+    }
+
+    /// Anular una serie atascada en una partida sin resolver
+    /// Cualquiera de los dos jugadores puede ejecutarlo cuando la partida en curso
+    /// lleva más de `SERIES_GAME_TIMEOUT_SECONDS` empezada: la partida queda anulada
+    /// y cada jugador recupera su depósito íntegro, sin fee
+    pub fn timeout_series(ctx: Context<TimeoutSeries>) -> Result<()> {
+        let series = &mut ctx.accounts.match_series;
+        let game = &mut ctx.accounts.game_match;
+        let player = ctx.accounts.player.key();
+        
+        require!(series.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            player == series.player1 || player == series.player2,
+            ErrorCode::NotAPlayer
+        );
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            series.current_game_id == Some(game.game_id),
+            ErrorCode::NotSeriesGame
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(
+            current_time >= game.started_at.checked_add(SERIES_GAME_TIMEOUT_SECONDS).unwrap(),
+            ErrorCode::TimeoutNotReached
+        );
+        
+        for (to, deposit) in [
+            (ctx.accounts.player1_token_account.to_account_info(), series.player1_deposit),
+            (ctx.accounts.player2_token_account.to_account_info(), series.player2_deposit),
+        ] {
+            if deposit > 0 {
+                transfer_from_escrow_account(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.escrow_token_account,
+                    &ctx.accounts.mint,
+                    series,
+                    to,
+                    deposit,
+                )?;
+            }
+        }
+        
+        game.status = GameStatus::Cancelled;
+        game.ended_at = current_time;
+        series.current_game_id = None;
+        series.status = GameStatus::Cancelled;
+        series.ended_at = current_time;
+        
+        emit!(SeriesTimedOut {
+            series_id: series.series_id,
+            game_id: game.game_id,
+            player1_refund: series.player1_deposit,
+            player2_refund: series.player2_deposit,
+            timestamp: current_time,
+        });
+        
+        msg!("Series {} voided: game {} never resolved. Both players refunded", 
+            series.series_id, game.game_id);
+        Ok(())
+    }
+
+    // ============= TOURNAMENT MODULE =============
+    // Torneos eliminatorios y suizos con inscripción en escrow y tabla de premios
+
+    /// Crear un torneo
+    /// `prize_table` son los basis points del prize pool para cada puesto (p. ej. [5000, 3000, 2000]);
+    /// `swiss_rounds` solo aplica al formato suizo (en eliminatoria se calcula al empezar)
+    pub fn create_tournament(
+        ctx: Context<CreateTournament>,
+        tournament_id: u64,
+        format: TournamentFormat,
+        entry_fee: u64,
+        max_players: u8,
+        swiss_rounds: u8,
+        prize_table: Vec<u16>,
+        time_control: TimeControl,
+        registration_deadline: i64,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let tournament = &mut ctx.accounts.tournament;
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        require!(!state.is_paused, ErrorCode::ProgramPaused);
+        require!(
+            entry_fee >= ctx.accounts.wager_mint.min_stake && entry_fee <= ctx.accounts.wager_mint.max_stake,
+            ErrorCode::StakeOutOfRange
+        );
+        require!(
+            max_players >= 2 && max_players as usize <= MAX_TOURNAMENT_PLAYERS,
+            ErrorCode::InvalidTournamentSize
+        );
+        require!(
+            format != TournamentFormat::Swiss || (swiss_rounds > 0 && swiss_rounds <= MAX_SWISS_ROUNDS),
+            ErrorCode::InvalidTournamentSize
+        );
+        require!(
+            !prize_table.is_empty()
+                && prize_table.len() <= MAX_PRIZE_PLACES
+                && prize_table.len() <= max_players as usize,
+            ErrorCode::InvalidPrizeTable
+        );
+        require!(
+            prize_table.iter().map(|bps| *bps as u32).sum::<u32>() == 10000,
+            ErrorCode::InvalidPrizeTable
+        );
+        require!(is_valid_time_control(&time_control), ErrorCode::InvalidTimeControl);
+        require!(registration_deadline > current_time, ErrorCode::InvalidDeadline);
+        
+        tournament.tournament_id = tournament_id;
+        tournament.organizer = ctx.accounts.organizer.key();
+        tournament.format = format;
+        tournament.mint = ctx.accounts.mint.key();
+        tournament.entry_fee = entry_fee;
+        tournament.max_players = max_players;
+        tournament.players = Vec::new();
+        tournament.prize_table = prize_table.clone();
+        tournament.time_control = time_control;
+        tournament.total_pot = 0;
+        tournament.total_rounds = if format == TournamentFormat::Swiss { swiss_rounds } else { 0 };
+        tournament.current_round = 0;
+        tournament.round_in_progress = false;
+        tournament.pending_results = 0;
+        tournament.status = TournamentStatus::Registration;
+        tournament.registration_deadline = registration_deadline;
+        tournament.created_at = current_time;
+        tournament.started_at = 0;
+        tournament.ended_at = 0;
+        tournament.round_deadline = 0;
+        tournament.escrow_bump = ctx.bumps.escrow_token_account;
+        
+        emit!(TournamentCreated {
+            tournament_id,
+            organizer: tournament.organizer,
+            format,
+            mint: tournament.mint,
+            entry_fee,
+            max_players,
+            prize_table,
+            registration_deadline,
+            timestamp: current_time,
+        });
+        
+        msg!("Tournament {} created: {:?}, entry fee {}", tournament_id, format, entry_fee);
+        Ok(())
+    }
+
+    /// Inscribirse en un torneo pagando la inscripción al escrow del torneo
+    pub fn register_tournament(ctx: Context<RegisterTournament>) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let tournament = &mut ctx.accounts.tournament;
+        let player = ctx.accounts.player.key();
+        
+        require!(!state.is_paused, ErrorCode::ProgramPaused);
+        require!(
+            tournament.status == TournamentStatus::Registration,
+            ErrorCode::RegistrationClosed
+        );
+        require!(
+            Clock::get()?.unix_timestamp <= tournament.registration_deadline,
+            ErrorCode::RegistrationClosed
+        );
+        require!(
+            tournament.players.len() < tournament.max_players as usize,
+            ErrorCode::TournamentFull
+        );
+        require!(
+            !tournament.players.iter().any(|p| p.player == player),
+            ErrorCode::AlreadyRegistered
+        );
+        // El organizador empareja las rondas, así que no puede jugar su propio torneo
+        require!(player != tournament.organizer, ErrorCode::OrganizerCannotPlay);
+        require!(
+            !is_blacklisted(&ctx.accounts.blacklist, &player),
+            ErrorCode::AddressBlacklisted
+        );
+        
+        let escrow_before = ctx.accounts.escrow_token_account.amount;
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.player_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.escrow_token_account.to_account_info(),
+            authority: ctx.accounts.player.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token_interface::transfer_checked(cpi_ctx, tournament.entry_fee, ctx.accounts.mint.decimals)?;
+        
+        ctx.accounts.escrow_token_account.reload()?;
+        let deposited = ctx.accounts.escrow_token_account.amount
+            .checked_sub(escrow_before).unwrap();
+        
+        tournament.players.push(TournamentPlayer {
+            player,
+            deposit: deposited,
+            score: 0,
+            eliminated: false,
+        });
+        tournament.total_pot = tournament.total_pot.checked_add(deposited).unwrap();
+        
+        emit!(TournamentPlayerRegistered {
+            tournament_id: tournament.tournament_id,
+            player,
+            player_index: (tournament.players.len() - 1) as u8,
+            total_pot: tournament.total_pot,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Player {} registered for tournament {}", player, tournament.tournament_id);
+        Ok(())
+    }
+
+    /// Cerrar la inscripción y empezar el torneo
+    /// Solo el organizador. En eliminatoria el número de rondas es ceil(log2(jugadores))
+    pub fn start_tournament(ctx: Context<OrganizeTournament>) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+        
+        require!(
+            ctx.accounts.organizer.key() == tournament.organizer,
+            ErrorCode::Unauthorized
+        );
+        require!(
+            tournament.status == TournamentStatus::Registration,
+            ErrorCode::RegistrationClosed
+        );
+        require!(
+            tournament.players.len() >= 2 && tournament.players.len() >= tournament.prize_table.len(),
+            ErrorCode::NotEnoughPlayers
+        );
+        
+        if tournament.format == TournamentFormat::Knockout {
+            tournament.total_rounds = knockout_rounds(tournament.players.len());
+        }
+        tournament.status = TournamentStatus::Running;
+        tournament.started_at = Clock::get()?.unix_timestamp;
+        tournament.round_deadline = tournament.started_at
+            .checked_add(TOURNAMENT_ROUND_TIMEOUT_SECONDS).unwrap();
+        
+        emit!(TournamentStarted {
+            tournament_id: tournament.tournament_id,
+            players: tournament.players.len() as u8,
+            total_rounds: tournament.total_rounds,
+            total_pot: tournament.total_pot,
+            timestamp: tournament.started_at,
+        });
+        
+        msg!("Tournament {} started with {} players, {} rounds", 
+            tournament.tournament_id, tournament.players.len(), tournament.total_rounds);
+        Ok(())
+    }
+
+    /// Publicar los emparejamientos de la siguiente ronda
+    /// Solo el organizador. Cada jugador activo aparece exactamente una vez;
+    /// `black = TOURNAMENT_BYE` es un bye (cuenta como victoria)
+    pub fn pair_round(ctx: Context<PairRound>, round: u8, pairings: Vec<PairingInput>) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+        let tournament_round = &mut ctx.accounts.tournament_round;
+        
+        require!(
+            ctx.accounts.organizer.key() == tournament.organizer,
+            ErrorCode::Unauthorized
+        );
+        require!(tournament.status == TournamentStatus::Running, ErrorCode::TournamentNotRunning);
+        require!(!tournament.round_in_progress, ErrorCode::RoundInProgress);
+        require!(
+            round == tournament.current_round + 1 && round <= tournament.total_rounds,
+            ErrorCode::InvalidRound
+        );
+        require!(pairings.len() <= MAX_PAIRINGS, ErrorCode::InvalidPairings);
+        
+        // Validar que cada jugador activo aparece exactamente una vez
+        let mut seen = [false; MAX_TOURNAMENT_PLAYERS];
+        for pairing in pairings.iter() {
+            let indices: &[u8] = if pairing.black == TOURNAMENT_BYE {
+                &[pairing.white]
+            } else {
+                &[pairing.white, pairing.black]
+            };
+            for index in indices {
+                let i = *index as usize;
+                require!(i < tournament.players.len(), ErrorCode::InvalidPairings);
+                require!(!seen[i] && !tournament.players[i].eliminated, ErrorCode::InvalidPairings);
+                seen[i] = true;
+            }
+        }
+        let all_paired = tournament.players.iter().enumerate()
+            .all(|(i, p)| p.eliminated || seen[i]);
+        require!(all_paired, ErrorCode::InvalidPairings);
+        
+        let mut pending = 0u8;
+        tournament_round.tournament_id = tournament.tournament_id;
+        tournament_round.round = round;
+        tournament_round.pairings = Vec::new();
+        for pairing in pairings.iter() {
+            let result = if pairing.black == TOURNAMENT_BYE {
+                tournament.players[pairing.white as usize].score += 2;
+                PairingResult::Bye
+            } else {
+                pending += 1;
+                PairingResult::Pending
+            };
+            tournament_round.pairings.push(Pairing {
+                white: pairing.white,
+                black: pairing.black,
+                game_id: pairing.game_id,
+                result,
+            });
+        }
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        tournament.current_round = round;
+        tournament.pending_results = pending;
+        tournament.round_in_progress = pending > 0;
+        tournament.round_deadline = current_time
+            .checked_add(TOURNAMENT_ROUND_TIMEOUT_SECONDS).unwrap();
+        
+        emit!(RoundPaired {
+            tournament_id: tournament.tournament_id,
+            round,
+            pairings: tournament_round.pairings.clone(),
+            timestamp: current_time,
+        });
+        
+        msg!("Tournament {}: round {} paired ({} games)", tournament.tournament_id, round, pending);
+        Ok(())
+    }
+
+    /// Registrar el resultado de un emparejamiento
+    /// Solo la autoridad del programa: el organizador no arbitra los resultados de su torneo.
+    /// En eliminatoria no se admiten tablas (el desempate se decide fuera)
+    pub fn report_pairing_result(
+        ctx: Context<ReportPairingResult>,
+        pairing_index: u8,
+        result: PairingResult,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let tournament = &mut ctx.accounts.tournament;
+        let tournament_round = &mut ctx.accounts.tournament_round;
+        
+        require!(ctx.accounts.authority.key() == state.authority, ErrorCode::Unauthorized);
+        require!(tournament.status == TournamentStatus::Running, ErrorCode::TournamentNotRunning);
+        require!(
+            tournament_round.round == tournament.current_round,
+            ErrorCode::InvalidRound
+        );
+        require!(
+            matches!(result, PairingResult::WhiteWins | PairingResult::BlackWins | PairingResult::Draw),
+            ErrorCode::InvalidPairingResult
+        );
+        require!(
+            tournament.format == TournamentFormat::Swiss || result != PairingResult::Draw,
+            ErrorCode::InvalidPairingResult
+        );
+        
+        let pairing = tournament_round.pairings.get_mut(pairing_index as usize)
+            .ok_or(ErrorCode::InvalidPairings)?;
+        require!(pairing.result == PairingResult::Pending, ErrorCode::ResultAlreadyReported);
+        pairing.result = result;
+        
+        let white = pairing.white as usize;
+        let black = pairing.black as usize;
+        match result {
+            PairingResult::WhiteWins => {
+                tournament.players[white].score += 2;
+                if tournament.format == TournamentFormat::Knockout {
+                    tournament.players[black].eliminated = true;
+                }
+            }
+            PairingResult::BlackWins => {
+                tournament.players[black].score += 2;
+                if tournament.format == TournamentFormat::Knockout {
+                    tournament.players[white].eliminated = true;
+                }
+            }
+            _ => {
+                tournament.players[white].score += 1;
+                tournament.players[black].score += 1;
+            }
+        }
+        
+        tournament.pending_results -= 1;
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        emit!(PairingResultReported {
+            tournament_id: tournament.tournament_id,
+            round: tournament_round.round,
+            pairing_index,
+            white: tournament.players[white].player,
+            black: tournament.players[black].player,
+            result,
+            timestamp: current_time,
+        });
+        
+        if tournament.pending_results == 0 {
+            tournament.round_in_progress = false;
+            tournament.round_deadline = current_time
+                .checked_add(TOURNAMENT_ROUND_TIMEOUT_SECONDS).unwrap();
+            emit!(RoundCompleted {
+                tournament_id: tournament.tournament_id,
+                round: tournament_round.round,
+                timestamp: current_time,
+            });
+        }
+        
+        Ok(())
+    }
+
+    /// Pagar el torneo según la tabla de premios, con el fee del DAO cobrado una sola vez
+    /// Cualquiera puede ejecutarlo cuando la última ronda está completa.
+    /// `remaining_accounts`: cuentas de token de los premiados en orden de clasificación
+    /// (empates resueltos por orden de inscripción)
+    pub fn finalize_tournament<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FinalizeTournament<'info>>,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let tournament = &mut ctx.accounts.tournament;
+        
+        require!(tournament.status == TournamentStatus::Running, ErrorCode::TournamentNotRunning);
+        require!(!tournament.round_in_progress, ErrorCode::RoundInProgress);
+        require!(
+            tournament.current_round == tournament.total_rounds,
+            ErrorCode::TournamentNotFinished
+        );
+        
+        let standings = tournament_standings(tournament);
+        let places = tournament.prize_table.len();
+        require!(ctx.remaining_accounts.len() == places, ErrorCode::InvalidTokenAccount);
+        
+        // Fee del DAO sobre el bote completo, una sola vez
+        let fee_basis_points = game_fee_basis_points(state, tournament.entry_fee, false);
+        let dao_fee = tournament.total_pot
+            .checked_mul(fee_basis_points as u64).unwrap()
+            .checked_div(10000).unwrap();
+        let prize_pool = tournament.total_pot.checked_sub(dao_fee).unwrap();
+        
+        let prizes = prize_shares(prize_pool, &tournament.prize_table);
+        
+        let mut winners = Vec::with_capacity(places);
+        for (place, (info, prize)) in ctx.remaining_accounts.iter().zip(prizes.iter()).enumerate() {
+            let player = tournament.players[standings[place]].player;
+            let token_account = InterfaceAccount::<TokenAccount>::try_from(info)?;
+            require!(
+                token_account.owner == player && token_account.mint == tournament.mint,
+                ErrorCode::InvalidTokenAccount
+            );
+            if *prize > 0 {
+                transfer_from_escrow_account(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.escrow_token_account,
+                    &ctx.accounts.mint,
+                    tournament,
+                    info.clone(),
+                    *prize,
+                )?;
+            }
+            winners.push(player);
+        }
+        
+        let fee_distribution = distribute_escrow_fee(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.fees,
+            tournament,
+            &state.fee_split,
+            dao_fee,
+        )?;
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        tournament.status = TournamentStatus::Completed;
+        tournament.ended_at = current_time;
+        
+        emit!(TournamentCompleted {
+            tournament_id: tournament.tournament_id,
+            winners,
+            prizes,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            timestamp: current_time,
+        });
+        
+        msg!("Tournament {} completed. Fee: {}", tournament.tournament_id, dao_fee);
+        Ok(())
+    }
+
+    /// Cancelar un torneo y devolver las inscripciones
+    /// El organizador puede cancelarlo en cualquier momento de la inscripción;
+    /// cualquiera puede hacerlo si pasó la fecha límite sin jugadores suficientes,
+    /// o con el torneo en marcha si venció el plazo de la ronda sin avanzar
+    /// (salvo que ya se pueda finalizar).
+    /// `remaining_accounts`: cuentas de token de los inscritos en orden de inscripción
+    pub fn cancel_tournament<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CancelTournament<'info>>,
+    ) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        match tournament.status {
+            TournamentStatus::Registration => {
+                let undersubscribed = current_time > tournament.registration_deadline
+                    && (tournament.players.len() < 2 || tournament.players.len() < tournament.prize_table.len());
+                require!(
+                    ctx.accounts.signer.key() == tournament.organizer || undersubscribed,
+                    ErrorCode::Unauthorized
+                );
+            }
+            TournamentStatus::Running => {
+                // Torneo abandonado: nadie emparejó o reportó a tiempo. Si ya se jugó
+                // la última ronda no se reembolsa: cualquiera puede finalizarlo
+                let finalizable = !tournament.round_in_progress
+                    && tournament.current_round == tournament.total_rounds;
+                require!(!finalizable, ErrorCode::TournamentFinalizable);
+                require!(
+                    current_time > tournament.round_deadline,
+                    ErrorCode::TournamentNotStalled
+                );
+            }
+            _ => return err!(ErrorCode::TournamentNotRunning),
+        }
+        require!(
+            ctx.remaining_accounts.len() == tournament.players.len(),
+            ErrorCode::InvalidTokenAccount
+        );
+        
+        for (info, entry) in ctx.remaining_accounts.iter().zip(tournament.players.iter()) {
+            let token_account = InterfaceAccount::<TokenAccount>::try_from(info)?;
+            require!(
+                token_account.owner == entry.player && token_account.mint == tournament.mint,
+                ErrorCode::InvalidTokenAccount
+            );
+            transfer_from_escrow_account(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &ctx.accounts.mint,
+                tournament,
+                info.clone(),
+                entry.deposit,
+            )?;
+        }
+        
+        tournament.status = TournamentStatus::Cancelled;
+        tournament.ended_at = current_time;
+        
+        emit!(TournamentCancelled {
+            tournament_id: tournament.tournament_id,
+            refunded_players: tournament.players.len() as u8,
+            timestamp: current_time,
+        });
+        
+        msg!("Tournament {} cancelled. Refunded {} players", 
+            tournament.tournament_id, tournament.players.len());
+        Ok(())
+    }
+
+    // ============= ARENA MODULE =============
+    // Arenas por tiempo: los jugadores se emparejan sin parar hasta que acaba el reloj
+    // y suman puntos por partida (con bonus por racha)
+
+    /// Crear una arena
+    /// `prize_table` reparte el prize pool entre los K primeros de la clasificación
+    pub fn create_arena(
+        ctx: Context<CreateArena>,
+        arena_id: u64,
+        entry_fee: u64,
+        start_time: i64,
+        end_time: i64,
+        max_players: u8,
+        prize_table: Vec<u16>,
+        time_control: TimeControl,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let arena = &mut ctx.accounts.arena;
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        require!(!state.is_paused, ErrorCode::ProgramPaused);
+        require!(
+            entry_fee >= ctx.accounts.wager_mint.min_stake && entry_fee <= ctx.accounts.wager_mint.max_stake,
+            ErrorCode::StakeOutOfRange
+        );
+        require!(
+            max_players >= 2 && max_players as usize <= MAX_ARENA_PLAYERS,
+            ErrorCode::InvalidTournamentSize
+        );
+        require!(
+            !prize_table.is_empty()
+                && prize_table.len() <= MAX_PRIZE_PLACES
+                && prize_table.len() <= max_players as usize,
+            ErrorCode::InvalidPrizeTable
+        );
+        require!(
+            prize_table.iter().map(|bps| *bps as u32).sum::<u32>() == 10000,
+            ErrorCode::InvalidPrizeTable
+        );
+        require!(is_valid_time_control(&time_control), ErrorCode::InvalidTimeControl);
+        require!(
+            start_time >= current_time
+                && end_time > start_time
+                && end_time - start_time <= MAX_ARENA_DURATION_SECONDS,
+            ErrorCode::InvalidDeadline
+        );
+        
+        arena.arena_id = arena_id;
+        arena.organizer = ctx.accounts.organizer.key();
+        arena.mint = ctx.accounts.mint.key();
+        arena.entry_fee = entry_fee;
+        arena.start_time = start_time;
+        arena.end_time = end_time;
+        arena.max_players = max_players;
+        arena.players = Vec::new();
+        arena.prize_table = prize_table.clone();
+        arena.time_control = time_control;
+        arena.total_pot = 0;
+        arena.games_in_progress = 0;
+        arena.status = ArenaStatus::Open;
+        arena.created_at = current_time;
+        arena.ended_at = 0;
+        arena.escrow_bump = ctx.bumps.escrow_token_account;
+        
+        emit!(ArenaCreated {
+            arena_id,
+            organizer: arena.organizer,
+            mint: arena.mint,
+            entry_fee,
+            start_time,
+            end_time,
+            max_players,
+            prize_table,
+            timestamp: current_time,
+        });
+        
+        msg!("Arena {} created: {} - {}, entry fee {}", arena_id, start_time, end_time, entry_fee);
+        Ok(())
+    }
+
+    /// Inscribirse en una arena pagando la inscripción al escrow
+    /// Se admite hasta que acaba el reloj (entrar tarde solo deja menos tiempo para sumar)
+    pub fn join_arena(ctx: Context<JoinArena>) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let arena = &mut ctx.accounts.arena;
+        let player = ctx.accounts.player.key();
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        require!(!state.is_paused, ErrorCode::ProgramPaused);
+        require!(arena.status == ArenaStatus::Open, ErrorCode::ArenaNotOpen);
+        require!(current_time < arena.end_time, ErrorCode::ArenaEnded);
+        require!(
+            arena.players.len() < arena.max_players as usize,
+            ErrorCode::ArenaFull
+        );
+        require!(
+            !arena.players.iter().any(|p| p.player == player),
+            ErrorCode::AlreadyRegistered
+        );
+        require!(
+            !is_blacklisted(&ctx.accounts.blacklist, &player),
+            ErrorCode::AddressBlacklisted
+        );
+        
+        let escrow_before = ctx.accounts.escrow_token_account.amount;
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.player_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.escrow_token_account.to_account_info(),
+            authority: ctx.accounts.player.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token_interface::transfer_checked(cpi_ctx, arena.entry_fee, ctx.accounts.mint.decimals)?;
+        
+        ctx.accounts.escrow_token_account.reload()?;
+        let deposited = ctx.accounts.escrow_token_account.amount
+            .checked_sub(escrow_before).unwrap();
+        
+        arena.players.push(ArenaPlayer {
+            player,
+            deposit: deposited,
+            points: 0,
+            streak: 0,
+            games_played: 0,
+            in_game: false,
+        });
+        arena.total_pot = arena.total_pot.checked_add(deposited).unwrap();
+        
+        emit!(ArenaJoined {
+            arena_id: arena.arena_id,
+            player,
+            total_pot: arena.total_pot,
+            timestamp: current_time,
+        });
+        
+        msg!("Player {} joined arena {}", player, arena.arena_id);
+        Ok(())
+    }
+
+    /// Emparejar a dos jugadores libres de la arena en una partida nueva
+    /// Solo el organizador, mientras el reloj de la arena esté corriendo
+    pub fn start_arena_game(
+        ctx: Context<StartArenaGame>,
+        game_id: u64,
+        white: Pubkey,
+        black: Pubkey,
+    ) -> Result<()> {
+        let arena = &mut ctx.accounts.arena;
+        let game = &mut ctx.accounts.game_match;
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        require!(
+            ctx.accounts.organizer.key() == arena.organizer,
+            ErrorCode::Unauthorized
+        );
+        require!(arena.status == ArenaStatus::Open, ErrorCode::ArenaNotOpen);
+        require!(current_time >= arena.start_time, ErrorCode::ArenaNotStarted);
+        require!(current_time < arena.end_time, ErrorCode::ArenaEnded);
+        require!(white != black, ErrorCode::InvalidPairings);
+        
+        for key in [white, black] {
+            let entry = arena.players.iter_mut()
+                .find(|p| p.player == key)
+                .ok_or(ErrorCode::NotRegistered)?;
+            require!(!entry.in_game, ErrorCode::PlayerInGame);
+            entry.in_game = true;
+        }
+        arena.games_in_progress += 1;
+        
+        game.game_id = game_id;
+        game.player1 = white;
+        game.player2 = black;
+        game.time_control = arena.time_control;
+        game.status = GameStatus::Active;
+        game.mode = GameMode::Standard;
+        game.created_at = current_time;
+        game.started_at = current_time;
+        game.player1_time_left = arena.time_control.base_seconds as i64;
+        game.player2_time_left = arena.time_control.base_seconds as i64;
+        game.player1_to_move = true;
+        game.clock_running_since = current_time;
+        game.player1_last_active = current_time;
+        game.player2_last_active = current_time;
+        game.mint = arena.mint;
+        game.arena_id = Some(arena.arena_id);
+        game.rent_payer = ctx.accounts.organizer.key();
+        
+        emit!(ArenaGameStarted {
+            arena_id: arena.arena_id,
+            game_id,
+            white,
+            black,
+            timestamp: current_time,
+        });
+        
+        msg!("Arena {}: game {} started ({} vs {})", arena.arena_id, game_id, white, black);
+        Ok(())
+    }
+
+    /// Registrar el resultado de una partida de arena y actualizar la clasificación
+    /// Solo authority. Victoria = 2, tablas = 1; con racha de victorias los puntos se duplican.
+    /// Las partidas empezadas antes del final cuentan aunque acaben después
+    pub fn resolve_arena_game(
+        ctx: Context<ResolveArenaGame>,
+        winner_key: Pubkey,
+        is_draw: bool,
+        record: Option<GameRecord>,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let arena = &mut ctx.accounts.arena;
+        let game = &mut ctx.accounts.game_match;
+        
+        require!(
+            ctx.accounts.authority.key() == state.authority,
+            ErrorCode::Unauthorized
+        );
+        require!(arena.status == ArenaStatus::Open, ErrorCode::ArenaNotOpen);
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(game.arena_id == Some(arena.arena_id), ErrorCode::NotArenaGame);
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        // Resultado de la partida: sin movimiento de tokens
+        game.record = record;
+        game.ended_at = current_time;
+        if is_draw {
+            game.status = GameStatus::Draw;
+            game.winner = None;
+        } else {
+            require!(
+                winner_key == game.player1 || winner_key == game.player2,
+                ErrorCode::InvalidWinner
+            );
+            game.status = GameStatus::Completed;
+            game.winner = Some(winner_key);
+        }
+        
+        let mut awarded = [0u32; 2];
+        for (side, key) in [game.player1, game.player2].iter().enumerate() {
+            let entry = arena.players.iter_mut()
+                .find(|p| p.player == *key)
+                .ok_or(ErrorCode::NotRegistered)?;
+            let outcome = match game.winner {
+                None => ArenaOutcome::Draw,
+                Some(winner) if winner == *key => ArenaOutcome::Win,
+                Some(_) => ArenaOutcome::Loss,
+            };
+            awarded[side] = apply_arena_result(entry, outcome);
+        }
+        arena.games_in_progress -= 1;
+        
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: game.winner,
+            is_draw,
+            prize_amount: 0,
+            dao_fee: 0,
+            fee_basis_points: 0,
+            fee_distribution: FeeDistribution::default(),
+            record: game.record,
+            timestamp: current_time,
+        });
+        
+        emit!(ArenaScoreUpdated {
+            arena_id: arena.arena_id,
+            game_id: game.game_id,
+            white: game.player1,
+            black: game.player2,
+            white_points: awarded[0],
+            black_points: awarded[1],
+            timestamp: current_time,
+        });
+        
+        msg!("Arena {}: game {} resolved (+{} / +{})", 
+            arena.arena_id, game.game_id, awarded[0], awarded[1]);
+        Ok(())
+    }
+
+    /// Cerrar la arena cuando acaba el reloj y pagar a los K primeros
+    /// Cualquiera puede ejecutarlo una vez resueltas las partidas en curso, o sin
+    /// esperarlas cuando pasa `ARENA_RESULT_GRACE_SECONDS` tras el final.
+    /// `remaining_accounts`: cuentas de token de los premiados en orden de clasificación
+    /// (empates por orden de inscripción). Solo puntúa quien jugó al menos una partida;
+    /// si hay menos clasificados que puestos, la parte sin reclamar va al primero.
+    /// Si no se jugó ninguna partida se devuelven las inscripciones sin fee y
+    /// `remaining_accounts` son las cuentas de todos los inscritos en orden de inscripción
+    pub fn finalize_arena<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FinalizeArena<'info>>,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let arena = &mut ctx.accounts.arena;
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        require!(arena.status == ArenaStatus::Open, ErrorCode::ArenaNotOpen);
+        require!(current_time >= arena.end_time, ErrorCode::ArenaNotEnded);
+        // Pasado el plazo de gracia las partidas sin resolver no puntúan
+        let grace_over = current_time > arena.end_time
+            .checked_add(ARENA_RESULT_GRACE_SECONDS).unwrap();
+        require!(
+            arena.games_in_progress == 0 || grace_over,
+            ErrorCode::ArenaGamesInProgress
+        );
+        
+        let standings = arena_standings(arena);
+        if standings.is_empty() {
+            // Arena sin partidas: nadie ganó nada, se reembolsa a todos
+            require!(
+                ctx.remaining_accounts.len() == arena.players.len(),
+                ErrorCode::InvalidTokenAccount
+            );
+            for (info, entry) in ctx.remaining_accounts.iter().zip(arena.players.iter()) {
+                let token_account = InterfaceAccount::<TokenAccount>::try_from(info)?;
+                require!(
+                    token_account.owner == entry.player && token_account.mint == arena.mint,
+                    ErrorCode::InvalidTokenAccount
+                );
+                transfer_from_escrow_account(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.escrow_token_account,
+                    &ctx.accounts.mint,
+                    arena,
+                    info.clone(),
+                    entry.deposit,
+                )?;
+            }
+            
+            arena.status = ArenaStatus::Cancelled;
+            arena.ended_at = current_time;
+            
+            emit!(ArenaCancelled {
+                arena_id: arena.arena_id,
+                refunded_players: arena.players.len() as u8,
+                timestamp: current_time,
+            });
+            
+            msg!("Arena {} ended without games. Refunded {} players", 
+                arena.arena_id, arena.players.len());
+            return Ok(());
+        }
+        
+        let places = arena.prize_table.len().min(standings.len());
+        require!(ctx.remaining_accounts.len() == places, ErrorCode::InvalidTokenAccount);
+        
+        // Fee del DAO sobre el bote completo, una sola vez
+        let fee_basis_points = game_fee_basis_points(state, arena.entry_fee, false);
+        let dao_fee = arena.total_pot
+            .checked_mul(fee_basis_points as u64).unwrap()
+            .checked_div(10000).unwrap();
+        let prize_pool = arena.total_pot.checked_sub(dao_fee).unwrap();
+        let prizes = prize_shares(prize_pool, &arena.prize_table[..places]);
+        
+        let mut winners = Vec::with_capacity(places);
+        for (place, (info, prize)) in ctx.remaining_accounts.iter().zip(prizes.iter()).enumerate() {
+            let player = arena.players[standings[place]].player;
+            let token_account = InterfaceAccount::<TokenAccount>::try_from(info)?;
+            require!(
+                token_account.owner == player && token_account.mint == arena.mint,
+                ErrorCode::InvalidTokenAccount
+            );
+            if *prize > 0 {
+                transfer_from_escrow_account(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.escrow_token_account,
+                    &ctx.accounts.mint,
+                    arena,
+                    info.clone(),
+                    *prize,
+                )?;
+            }
+            winners.push(player);
+        }
+        
+        let fee_distribution = distribute_escrow_fee(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.fees,
+            arena,
+            &state.fee_split,
+            dao_fee,
+        )?;
+        
+        arena.status = ArenaStatus::Completed;
+        arena.ended_at = current_time;
+        
+        emit!(ArenaCompleted {
+            arena_id: arena.arena_id,
+            winners,
+            prizes,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            timestamp: current_time,
+        });
+        
+        msg!("Arena {} completed. Fee: {}", arena.arena_id, dao_fee);
+        Ok(())
+    }
+
+    /// Cancelar una arena antes de que empiece y devolver las inscripciones
+    /// Solo el organizador.
+    /// `remaining_accounts`: cuentas de token de los inscritos en orden de inscripción
+    pub fn cancel_arena<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CancelArena<'info>>,
+    ) -> Result<()> {
+        let arena = &mut ctx.accounts.arena;
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        require!(
+            ctx.accounts.organizer.key() == arena.organizer,
+            ErrorCode::Unauthorized
+        );
+        require!(arena.status == ArenaStatus::Open, ErrorCode::ArenaNotOpen);
+        require!(current_time < arena.start_time, ErrorCode::ArenaAlreadyStarted);
+        require!(
+            ctx.remaining_accounts.len() == arena.players.len(),
+            ErrorCode::InvalidTokenAccount
+        );
+        
+        for (info, entry) in ctx.remaining_accounts.iter().zip(arena.players.iter()) {
+            let token_account = InterfaceAccount::<TokenAccount>::try_from(info)?;
+            require!(
+                token_account.owner == entry.player && token_account.mint == arena.mint,
+                ErrorCode::InvalidTokenAccount
+            );
+            transfer_from_escrow_account(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &ctx.accounts.mint,
+                arena,
+                info.clone(),
+                entry.deposit,
+            )?;
+        }
+        
+        arena.status = ArenaStatus::Cancelled;
+        arena.ended_at = current_time;
+        
+        emit!(ArenaCancelled {
+            arena_id: arena.arena_id,
+            refunded_players: arena.players.len() as u8,
+            timestamp: current_time,
+        });
+        
+        msg!("Arena {} cancelled. Refunded {} players", arena.arena_id, arena.players.len());
+        Ok(())
+    }
+
+    // ============= SQUAD MODULE =============
+    // Squads con líder, miembros y vault de tokens, y batallas entre squads
+
+    /// Crear un squad con su vault de tokens
+    /// El líder es el primer miembro
+    pub fn create_squad(ctx: Context<CreateSquad>, squad_id: u64, name: String) -> Result<()> {
+        let squad = &mut ctx.accounts.squad;
+        let leader = ctx.accounts.leader.key();
+        
+        require!(name.len() <= MAX_SQUAD_NAME_LENGTH, ErrorCode::SquadNameTooLong);
+        
+        squad.squad_id = squad_id;
+        squad.leader = leader;
+        squad.name = name.clone();
+        squad.mint = ctx.accounts.mint.key();
+        squad.members = vec![leader];
+        squad.active_battles = 0;
+        squad.created_at = Clock::get()?.unix_timestamp;
+        squad.vault_bump = ctx.bumps.vault_token_account;
+        
+        emit!(SquadCreated {
+            squad_id,
+            leader,
+            name,
+            mint: squad.mint,
+            timestamp: squad.created_at,
+        });
+        
+        msg!("Squad {} created by {}", squad_id, leader);
+        Ok(())
+    }
+
+    /// Añadir un miembro al squad (firman el líder y el nuevo miembro); no durante una batalla
+    pub fn join_squad(ctx: Context<JoinSquad>) -> Result<()> {
+        let squad = &mut ctx.accounts.squad;
+        let member = ctx.accounts.member.key();
+        
+        require!(ctx.accounts.leader.key() == squad.leader, ErrorCode::Unauthorized);
+        require!(!squad.members.contains(&member), ErrorCode::AlreadySquadMember);
+        require!(squad.members.len() < MAX_SQUAD_MEMBERS, ErrorCode::SquadFull);
+        require!(squad.active_battles == 0, ErrorCode::SquadInBattle);
+        
+        squad.members.push(member);
+        
+        emit!(SquadMemberJoined {
+            squad_id: squad.squad_id,
+            member,
+            members: squad.members.len() as u8,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("{} joined squad {}", member, squad.squad_id);
+        Ok(())
+    }
+
+    /// Sacar a un miembro del squad
+    /// El líder puede expulsar y cualquier miembro puede salir; no durante una batalla
+    pub fn leave_squad(ctx: Context<LeaveSquad>, member: Pubkey) -> Result<()> {
+        let squad = &mut ctx.accounts.squad;
+        let signer = ctx.accounts.signer.key();
+        
+        require!(
+            signer == squad.leader || signer == member,
+            ErrorCode::Unauthorized
+        );
+        require!(member != squad.leader, ErrorCode::Unauthorized);
+        require!(squad.active_battles == 0, ErrorCode::SquadInBattle);
+        
+        let index = squad.members.iter()
+            .position(|m| *m == member)
+            .ok_or(ErrorCode::NotSquadMember)?;
+        squad.members.remove(index);
+        
+        emit!(SquadMemberLeft {
+            squad_id: squad.squad_id,
+            member,
+            removed_by: signer,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("{} left squad {}", member, squad.squad_id);
+        Ok(())
+    }
+
+    /// Aportar tokens al vault del squad (cualquiera puede aportar)
+    pub fn fund_squad_vault(ctx: Context<FundSquadVault>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        
+        let vault_before = ctx.accounts.vault_token_account.amount;
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.funder_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.vault_token_account.to_account_info(),
+            authority: ctx.accounts.funder.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
+        
+        ctx.accounts.vault_token_account.reload()?;
+        let deposited = ctx.accounts.vault_token_account.amount
+            .checked_sub(vault_before).unwrap();
+        
+        emit!(SquadVaultFunded {
+            squad_id: ctx.accounts.squad.squad_id,
+            funder: ctx.accounts.funder.key(),
+            amount: deposited,
+            vault_balance: ctx.accounts.vault_token_account.amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Squad {} vault funded with {}", ctx.accounts.squad.squad_id, deposited);
+        Ok(())
+    }
+
+    /// Repartir tokens del vault a un miembro del squad
+    /// Solo el líder
+    pub fn distribute_squad_vault(ctx: Context<DistributeSquadVault>, amount: u64) -> Result<()> {
+        let squad = &ctx.accounts.squad;
+        let recipient = ctx.accounts.recipient_token_account.owner;
+        
+        require!(ctx.accounts.leader.key() == squad.leader, ErrorCode::Unauthorized);
+        require!(squad.members.contains(&recipient), ErrorCode::NotSquadMember);
+        require!(
+            amount > 0 && amount <= ctx.accounts.vault_token_account.amount,
+            ErrorCode::InvalidAmount
+        );
+        
+        transfer_from_escrow_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault_token_account,
+            &ctx.accounts.mint,
+            squad,
+            ctx.accounts.recipient_token_account.to_account_info(),
+            amount,
+        )?;
+        
+        emit!(SquadVaultDistributed {
+            squad_id: squad.squad_id,
+            recipient,
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Squad {}: {} distributed to {}", squad.squad_id, amount, recipient);
+        Ok(())
+    }
+
+    /// Retar a otro squad a una batalla por equipos
+    /// Solo el líder del squad retador; su stake sale del vault del squad
+    pub fn create_team_battle(
+        ctx: Context<CreateTeamBattle>,
+        battle_id: u64,
+        stake: u64,
+        end_time: i64,
+        time_control: TimeControl,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let squad_a = &ctx.accounts.squad_a;
+        let squad_b = &ctx.accounts.squad_b;
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        require!(!state.is_paused, ErrorCode::ProgramPaused);
+        require!(ctx.accounts.leader.key() == squad_a.leader, ErrorCode::Unauthorized);
+        require!(squad_a.squad_id != squad_b.squad_id, ErrorCode::SameSquad);
+        require!(squad_b.mint == squad_a.mint, ErrorCode::MintNotAllowed);
+        require!(
+            stake >= ctx.accounts.wager_mint.min_stake && stake <= ctx.accounts.wager_mint.max_stake,
+            ErrorCode::StakeOutOfRange
+        );
+        require!(is_valid_time_control(&time_control), ErrorCode::InvalidTimeControl);
+        require!(
+            end_time > current_time && end_time - current_time <= MAX_TEAM_BATTLE_DURATION_SECONDS,
+            ErrorCode::InvalidDeadline
+        );
+        
+        let escrow_before = ctx.accounts.escrow_token_account.amount;
+        transfer_from_escrow_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.squad_a_vault,
+            &ctx.accounts.mint,
+            squad_a,
+            ctx.accounts.escrow_token_account.to_account_info(),
+            stake,
+        )?;
+        ctx.accounts.escrow_token_account.reload()?;
+        let deposited = ctx.accounts.escrow_token_account.amount
+            .checked_sub(escrow_before).unwrap();
+        
+        let battle = &mut ctx.accounts.team_battle;
+        battle.battle_id = battle_id;
+        battle.squad_a = squad_a.squad_id;
+        battle.squad_b = squad_b.squad_id;
+        battle.mint = squad_a.mint;
+        battle.stake = stake;
+        battle.squad_a_deposit = deposited;
+        battle.squad_b_deposit = 0;
+        battle.total_pot = deposited;
+        battle.squad_a_score = 0;
+        battle.squad_b_score = 0;
+        battle.games_played = 0;
+        battle.games_in_progress = 0;
+        battle.time_control = time_control;
+        battle.end_time = end_time;
+        battle.status = GameStatus::Waiting;
+        battle.winner_squad = None;
+        battle.created_at = current_time;
+        battle.started_at = 0;
+        battle.ended_at = 0;
+        battle.escrow_bump = ctx.bumps.escrow_token_account;
+        
+        emit!(TeamBattleCreated {
+            battle_id,
+            squad_a: battle.squad_a,
+            squad_b: battle.squad_b,
+            mint: battle.mint,
+            stake,
+            end_time,
+            timestamp: current_time,
+        });
+        
+        msg!("Team battle {} created: squad {} vs squad {}, stake {}", 
+            battle_id, battle.squad_a, battle.squad_b, stake);
+        Ok(())
+    }
+
+    /// Aceptar una batalla por equipos
+    /// Solo el líder del squad retado; su stake sale del vault del squad
+    pub fn accept_team_battle(ctx: Context<AcceptTeamBattle>) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        require!(!state.is_paused, ErrorCode::ProgramPaused);
+        require!(
+            ctx.accounts.leader.key() == ctx.accounts.squad_b.leader,
+            ErrorCode::Unauthorized
+        );
+        require!(
+            ctx.accounts.team_battle.status == GameStatus::Waiting,
+            ErrorCode::GameNotWaiting
+        );
+        require!(current_time < ctx.accounts.team_battle.end_time, ErrorCode::BattleEnded);
+        
+        let escrow_before = ctx.accounts.escrow_token_account.amount;
+        transfer_from_escrow_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.squad_b_vault,
+            &ctx.accounts.mint,
+            &ctx.accounts.squad_b,
+            ctx.accounts.escrow_token_account.to_account_info(),
+            ctx.accounts.team_battle.stake,
+        )?;
+        ctx.accounts.escrow_token_account.reload()?;
+        let deposited = ctx.accounts.escrow_token_account.amount
+            .checked_sub(escrow_before).unwrap();
+        
+        let battle = &mut ctx.accounts.team_battle;
+        battle.squad_b_deposit = deposited;
+        battle.total_pot = battle.total_pot.checked_add(deposited).unwrap();
+        battle.status = GameStatus::Active;
+        battle.started_at = current_time;
+        
+        // Sin cambios de miembros mientras dure la batalla
+        ctx.accounts.squad_a.active_battles += 1;
+        ctx.accounts.squad_b.active_battles += 1;
+        
+        emit!(TeamBattleAccepted {
+            battle_id: battle.battle_id,
+            squad_b: battle.squad_b,
+            total_pot: battle.total_pot,
+            timestamp: current_time,
+        });
+        
+        msg!("Team battle {} accepted. Total pot: {}", battle.battle_id, battle.total_pot);
+        Ok(())
+    }
+
+    /// Cancelar una batalla no aceptada y devolver el stake al vault del retador
+    pub fn cancel_team_battle(ctx: Context<CancelTeamBattle>) -> Result<()> {
+        let battle = &mut ctx.accounts.team_battle;
+        
+        require!(
+            ctx.accounts.leader.key() == ctx.accounts.squad_a.leader,
+            ErrorCode::Unauthorized
+        );
+        require!(battle.status == GameStatus::Waiting, ErrorCode::GameNotWaiting);
+        
+        let refund_amount = battle.squad_a_deposit;
+        transfer_from_escrow_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.mint,
+            battle,
+            ctx.accounts.squad_a_vault.to_account_info(),
+            refund_amount,
+        )?;
+        
+        battle.status = GameStatus::Cancelled;
+        battle.ended_at = Clock::get()?.unix_timestamp;
+        
+        emit!(TeamBattleCancelled {
+            battle_id: battle.battle_id,
+            squad_a: battle.squad_a,
+            refund_amount,
+            timestamp: battle.ended_at,
+        });
+        
+        msg!("Team battle {} cancelled. Refunded {} to squad {}", 
+            battle.battle_id, refund_amount, battle.squad_a);
+        Ok(())
+    }
+
+    /// Empezar una partida de la batalla entre un miembro de cada squad
+    /// Cualquiera de los dos líderes. La partida no tiene escrow propio:
+    /// se resuelve con resolve_battle_game
+    pub fn start_battle_game(
+        ctx: Context<StartBattleGame>,
+        game_id: u64,
+        white: Pubkey,
+        black: Pubkey,
+    ) -> Result<()> {
+        let battle = &mut ctx.accounts.team_battle;
+        let squad_a = &ctx.accounts.squad_a;
+        let squad_b = &ctx.accounts.squad_b;
+        let game = &mut ctx.accounts.game_match;
+        let signer = ctx.accounts.leader.key();
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        require!(
+            signer == squad_a.leader || signer == squad_b.leader,
+            ErrorCode::Unauthorized
+        );
+        require!(battle.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(current_time < battle.end_time, ErrorCode::BattleEnded);
+        
+        let white_side = battle_side(squad_a, squad_b, &white)?;
+        let black_side = battle_side(squad_a, squad_b, &black)?;
+        require!(white_side != black_side, ErrorCode::SameSquad);
+        
+        battle.games_in_progress += 1;
+        
+        game.game_id = game_id;
+        game.player1 = white;
+        game.player2 = black;
+        game.time_control = battle.time_control;
+        game.status = GameStatus::Active;
+        game.mode = GameMode::Standard;
+        game.created_at = current_time;
+        game.started_at = current_time;
+        game.player1_time_left = battle.time_control.base_seconds as i64;
+        game.player2_time_left = battle.time_control.base_seconds as i64;
+        game.player1_to_move = true;
+        game.clock_running_since = current_time;
+        game.player1_last_active = current_time;
+        game.player2_last_active = current_time;
+        game.mint = battle.mint;
+        game.team_battle_id = Some(battle.battle_id);
+        game.rent_payer = ctx.accounts.leader.key();
+        
+        emit!(BattleGameStarted {
+            battle_id: battle.battle_id,
+            game_id,
+            white,
+            black,
+            timestamp: current_time,
+        });
+        
+        msg!("Team battle {}: game {} started ({} vs {})", battle.battle_id, game_id, white, black);
+        Ok(())
+    }
+
+    /// Registrar el resultado de una partida de la batalla
+    /// Solo authority. Suma al marcador del squad: victoria = 2, tablas = 1 (medios puntos)
+    pub fn resolve_battle_game(
+        ctx: Context<ResolveBattleGame>,
+        winner_key: Pubkey,
+        is_draw: bool,
+        record: Option<GameRecord>,
+    ) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let battle = &mut ctx.accounts.team_battle;
+        let game = &mut ctx.accounts.game_match;
+        
+        require!(
+            ctx.accounts.authority.key() == state.authority,
+            ErrorCode::Unauthorized
+        );
+        require!(battle.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(game.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(
+            game.team_battle_id == Some(battle.battle_id),
+            ErrorCode::NotBattleGame
+        );
+        
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        // Resultado de la partida: sin movimiento de tokens
+        game.record = record;
+        game.ended_at = current_time;
+        if is_draw {
+            game.status = GameStatus::Draw;
+            game.winner = None;
+            battle.squad_a_score += 1;
+            battle.squad_b_score += 1;
+        } else {
+            require!(
+                winner_key == game.player1 || winner_key == game.player2,
+                ErrorCode::InvalidWinner
+            );
+            game.status = GameStatus::Completed;
+            game.winner = Some(winner_key);
+            match battle_side(&ctx.accounts.squad_a, &ctx.accounts.squad_b, &winner_key)? {
+                BattleSide::SquadA => battle.squad_a_score += 2,
+                BattleSide::SquadB => battle.squad_b_score += 2,
+            }
+        }
+        battle.games_played += 1;
+        battle.games_in_progress -= 1;
+        
+        emit!(GameResolved {
+            game_id: game.game_id,
+            winner: game.winner,
+            is_draw,
+            prize_amount: 0,
+            dao_fee: 0,
+            fee_basis_points: 0,
+            fee_distribution: FeeDistribution::default(),
+            record: game.record,
+            timestamp: current_time,
+        });
+        
+        emit!(TeamBattleScoreUpdated {
+            battle_id: battle.battle_id,
+            game_id: game.game_id,
+            games_played: battle.games_played,
+            squad_a_score: battle.squad_a_score,
+            squad_b_score: battle.squad_b_score,
+            timestamp: current_time,
+        });
+        
+        msg!("Team battle {}: {} - {} (half-points)", 
+            battle.battle_id, battle.squad_a_score, battle.squad_b_score);
+        Ok(())
+    }
+
+    /// Cerrar la batalla cuando acaba el tiempo y pagar el bote al vault del squad ganador
+    /// Cualquiera puede ejecutarlo una vez resueltas las partidas en curso, o sin
+    /// esperarlas cuando pasa `BATTLE_RESULT_GRACE_SECONDS` tras el final.
+    /// En empate cada vault recupera su parte proporcional a lo aportado; sin partidas
+    /// resueltas se reembolsa a ambos vaults íntegramente y sin fee
+    pub fn finalize_team_battle(ctx: Context<FinalizeTeamBattle>) -> Result<()> {
+        let state = &ctx.accounts.token_state;
+        let battle = &mut ctx.accounts.team_battle;
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        require!(battle.status == GameStatus::Active, ErrorCode::GameNotActive);
+        require!(current_time >= battle.end_time, ErrorCode::BattleNotEnded);
+        // Pasado el plazo de gracia las partidas sin resolver no puntúan
+        let grace_over = current_time > battle.end_time
+            .checked_add(BATTLE_RESULT_GRACE_SECONDS).unwrap();
+        require!(
+            battle.games_in_progress == 0 || grace_over,
+            ErrorCode::BattleGamesInProgress
+        );
+        
+        if battle.games_played == 0 {
+            // Batalla sin partidas resueltas: cada vault recupera lo aportado, sin fee
+            for (vault, deposit) in [
+                (ctx.accounts.squad_a_vault.to_account_info(), battle.squad_a_deposit),
+                (ctx.accounts.squad_b_vault.to_account_info(), battle.squad_b_deposit),
+            ] {
+                if deposit > 0 {
+                    transfer_from_escrow_account(
+                        &ctx.accounts.token_program,
+                        &ctx.accounts.escrow_token_account,
+                        &ctx.accounts.mint,
+                        battle,
+                        vault,
+                        deposit,
+                    )?;
+                }
+            }
+            
+            battle.status = GameStatus::Cancelled;
+            battle.ended_at = current_time;
+            ctx.accounts.squad_a.active_battles -= 1;
+            ctx.accounts.squad_b.active_battles -= 1;
+            
+            emit!(TeamBattleRefunded {
+                battle_id: battle.battle_id,
+                squad_a_refund: battle.squad_a_deposit,
+                squad_b_refund: battle.squad_b_deposit,
+                timestamp: current_time,
+            });
+            
+            msg!("Team battle {} ended without games. Both squads refunded", battle.battle_id);
+            return Ok(());
+        }
+        
+        let winner_squad = match battle.squad_a_score.cmp(&battle.squad_b_score) {
+            std::cmp::Ordering::Greater => Some(battle.squad_a),
+            std::cmp::Ordering::Less => Some(battle.squad_b),
+            std::cmp::Ordering::Equal => None,
+        };
+        let is_draw = winner_squad.is_none();
+        
+        let fee_basis_points = game_fee_basis_points(state, battle.stake, is_draw);
+        let dao_fee = battle.total_pot
+            .checked_mul(fee_basis_points as u64).unwrap()
+            .checked_div(10000).unwrap();
+        let prize_pool = battle.total_pot.checked_sub(dao_fee).unwrap();
+        
+        let (squad_a_prize, squad_b_prize) = match winner_squad {
+            None => {
+                let total_deposits = battle.squad_a_deposit as u128 + battle.squad_b_deposit as u128;
+                let squad_a_share = (prize_pool as u128 * battle.squad_a_deposit as u128
+                    / total_deposits.max(1)) as u64;
+                (squad_a_share, prize_pool - squad_a_share)
+            }
+            Some(winner) if winner == battle.squad_a => (prize_pool, 0),
+            Some(_) => (0, prize_pool),
+        };
+        
+        if squad_a_prize > 0 {
+            transfer_from_escrow_account(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &ctx.accounts.mint,
+                battle,
+                ctx.accounts.squad_a_vault.to_account_info(),
+                squad_a_prize,
+            )?;
+        }
+        if squad_b_prize > 0 {
+            transfer_from_escrow_account(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &ctx.accounts.mint,
+                battle,
+                ctx.accounts.squad_b_vault.to_account_info(),
+                squad_b_prize,
+            )?;
+        }
+        
+        let fee_distribution = distribute_escrow_fee(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.fees,
+            battle,
+            &state.fee_split,
+            dao_fee,
+        )?;
+        
+        battle.status = if is_draw { GameStatus::Draw } else { GameStatus::Completed };
+        battle.winner_squad = winner_squad;
+        battle.ended_at = current_time;
+        ctx.accounts.squad_a.active_battles -= 1;
+        ctx.accounts.squad_b.active_battles -= 1;
+        
+        emit!(TeamBattleCompleted {
+            battle_id: battle.battle_id,
+            winner_squad,
+            is_draw,
+            squad_a_score: battle.squad_a_score,
+            squad_b_score: battle.squad_b_score,
+            squad_a_prize,
+            squad_b_prize,
+            dao_fee,
+            fee_basis_points,
+            fee_distribution,
+            timestamp: current_time,
+        });
+        
+        msg!("Team battle {} finished. Winner squad: {:?}, Fee: {}", 
+            battle.battle_id, winner_squad, dao_fee);
+        Ok(())
+    }
+}
+
+// ============= ACCOUNTS =============
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TokenState::INIT_SPACE,
+        seeds = [b"token_state"],
+        bump
+    )]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Blacklist::INIT_SPACE,
+        seeds = [b"blacklist"],
+        bump
+    )]
+    pub blacklist: Account<'info, Blacklist>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MintTokens<'info> {
+    #[account(mut, seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"blacklist"], bump)]
+    pub blacklist: Account<'info, Blacklist>,
+    
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    /// CHECK: Mint authority PDA
+    pub mint_authority: AccountInfo<'info>,
+    
+    /// CHECK: Recipient address
+    pub recipient: AccountInfo<'info>,
+    
+    #[account(mut)]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct BurnTokens<'info> {
+    #[account(mut, seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SwapChessToGame<'info> {
+    #[account(mut, seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"blacklist"], bump)]
+    pub blacklist: Account<'info, Blacklist>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = 8 + SwapRecord::INIT_SPACE,
+        seeds = [b"swap", user.key().as_ref(), &Clock::get().unwrap().unix_timestamp.to_le_bytes()],
+        bump
+    )]
+    pub swap_record: Account<'info, SwapRecord>,
+    
+    #[account(mut)]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut, address = user_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = staking_token_account.key() == token_state.staking_pool @ ErrorCode::InvalidStakingPool
+    )]
+    pub staking_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    #[account(
+        mut,
+        constraint = referrer_token_account.owner != user.key() @ ErrorCode::SelfReferral
+    )]
+    pub referrer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdminAction<'info> {
+    #[account(mut, seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureWagerMint<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + WagerMint::INIT_SPACE,
+        seeds = [b"wager_mint", mint.key().as_ref()],
+        bump
+    )]
+    pub wager_mint: Account<'info, WagerMint>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateTokenState<'info> {
+    /// CHECK: puede tener el layout antiguo; el handler valida discriminador, tamaño y authority
+    #[account(mut, seeds = [b"token_state"], bump, owner = crate::ID)]
+    pub token_state: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageBlacklist<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(mut, seeds = [b"blacklist"], bump)]
+    pub blacklist: Account<'info, Blacklist>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferAuthority<'info> {
+    #[account(mut, seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(mut, seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(mut)]
+    pub new_authority: Signer<'info>,
+}
+
+// ============= GAME ESCROW ACCOUNTS =============
+
+/// Destinos del fee del DAO, comunes a todas las liquidaciones desde un escrow
+/// El contexto padre comprueba que `wager_mint.mint` sea el mint de su escrow;
+/// staking y referrer solo se exigen si su parte es > 0 (el referrer se valida al repartir)
+#[derive(Accounts)]
+pub struct FeeAccountsCtx<'info> {
+    #[account(seeds = [b"wager_mint", wager_mint.mint.as_ref()], bump)]
+    pub wager_mint: Account<'info, WagerMint>,
+    
+    #[account(
+        mut,
+        constraint = treasury_token_account.key() == wager_mint.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_token_account.key() == wager_mint.staking_pool @ ErrorCode::InvalidStakingPool
+    )]
+    pub staking_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    #[account(mut)]
+    pub referrer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(game_id: u64, bet_amount: u64, time_control: TimeControl, game_title: String, mode: GameMode)]
+pub struct CreateGame<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"blacklist"], bump)]
+    pub blacklist: Account<'info, Blacklist>,
+    
+    #[account(
+        init,
+        payer = player1,
+        space = 8 + GameMatch::INIT_SPACE,
+        seeds = [b"game", game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        init_if_needed,
+        payer = player1,
+        space = 8 + GameCounter::INIT_SPACE,
+        seeds = [b"game_counter", (game_id % GAME_COUNTER_SHARDS).to_le_bytes().as_ref()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        init,
+        payer = player1,
+        token::mint = mint,
+        token::authority = escrow_token_account,
+        token::token_program = token_program,
+        seeds = [b"game_escrow", game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [b"wager_mint", mint.key().as_ref()],
+        bump,
+        constraint = wager_mint.enabled @ ErrorCode::MintNotAllowed
+    )]
+    pub wager_mint: Account<'info, WagerMint>,
+    
+    #[account(mut)]
+    pub player1: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct JoinGame<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"blacklist"], bump)]
+    pub blacklist: Account<'info, Blacklist>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.mint == game_match.mint @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub player2: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveGame<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"game_config"], bump)]
+    pub game_config: Account<'info, GameConfig>,
+    
+    #[account(seeds = [b"oracle_registry"], bump)]
+    pub oracle_registry: Account<'info, OracleRegistry>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelGame<'info> {
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub player1: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireGame<'info> {
+    #[account(seeds = [b"game_config"], bump)]
+    pub game_config: Account<'info, GameConfig>,
+    
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    /// Solo necesaria si player2 ya depositó (revancha a medio financiar)
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    /// CHECK: PDA del tablero, obligatorio en partidas trustless; puede no existir
+    /// si player1 nunca llegó a crearlo. close_board valida dueño y discriminador
+    #[account(
+        mut,
+        seeds = [b"board", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub chess_board: Option<UncheckedAccount<'info>>,
+    
+    /// Quien pagó la partida y su escrow: player1, o quien abrió la revancha
+    #[account(mut, address = game_match.rent_payer)]
+    pub rent_payer: SystemAccount<'info>,
+    
+    /// Recibe la renta del tablero, que pagó player1
+    #[account(mut, address = game_match.player1)]
+    pub player1: SystemAccount<'info>,
+    
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CloseGame<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    /// Solo las partidas sueltas tienen escrow propio; las de serie, arena o batalla no
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    #[account(address = game_match.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    /// CHECK: PDA del tablero, obligatorio en partidas trustless; puede no existir
+    /// si player1 nunca llegó a crearlo. close_board valida dueño y discriminador
+    #[account(
+        mut,
+        seeds = [b"board", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub chess_board: Option<UncheckedAccount<'info>>,
+    
+    #[account(seeds = [b"wager_mint", game_match.mint.as_ref()], bump)]
+    pub wager_mint: Account<'info, WagerMint>,
+    
+    #[account(
+        mut,
+        constraint = treasury_token_account.key() == wager_mint.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut, address = game_match.player1)]
+    pub player1: SystemAccount<'info>,
+    
+    #[account(mut, address = game_match.rent_payer)]
+    pub rent_payer: SystemAccount<'info>,
+    
+    pub closer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct TimeoutClaim<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"game_config"], bump)]
+    pub game_config: Account<'info, GameConfig>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub claimer_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitResult<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub player: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// ============= ORACLE ACCOUNTS =============
+
+#[derive(Accounts)]
+pub struct InitializeOracleRegistry<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + OracleRegistry::INIT_SPACE,
+        seeds = [b"oracle_registry"],
+        bump
+    )]
+    pub oracle_registry: Account<'info, OracleRegistry>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageOracles<'info> {
+    #[account(mut, seeds = [b"oracle_registry"], bump)]
+    pub oracle_registry: Account<'info, OracleRegistry>,
+    
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveGameWithOracle<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"oracle_registry"], bump)]
+    pub oracle_registry: Account<'info, OracleRegistry>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    /// CHECK: Sysvar de instrucciones, validado por dirección
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+    
+    pub relayer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitOracleVote<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"oracle_registry"], bump)]
+    pub oracle_registry: Account<'info, OracleRegistry>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = 8 + PendingResult::INIT_SPACE,
+        seeds = [b"pending_result", game_match.game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub pending_result: Account<'info, PendingResult>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    /// CHECK: Sysvar de instrucciones, validado por dirección
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+    
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+    
+    /// Solo necesaria en el voto que alcanza el quórum: `pending_result.payer`
+    #[account(mut)]
+    pub pending_payer: Option<SystemAccount<'info>>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+// ============= OPTIMISTIC SETTLEMENT ACCOUNTS =============
+
+#[derive(Accounts)]
+pub struct InitializeGameConfig<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + GameConfig::INIT_SPACE,
+        seeds = [b"game_config"],
+        bump
+    )]
+    pub game_config: Account<'info, GameConfig>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateGameConfig<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(mut, seeds = [b"game_config"], bump)]
+    pub game_config: Account<'info, GameConfig>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeResult<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"game_config"], bump)]
+    pub game_config: Account<'info, GameConfig>,
+    
+    #[account(seeds = [b"oracle_registry"], bump)]
+    pub oracle_registry: Account<'info, OracleRegistry>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeSettlement<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub cranker: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct OpenDispute<'info> {
+    #[account(seeds = [b"game_config"], bump)]
+    pub game_config: Account<'info, GameConfig>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub player_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub player: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDispute<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"game_config"], bump)]
+    pub game_config: Account<'info, GameConfig>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub arbiter: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireDispute<'info> {
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub signer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitFraudProof<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub player: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// ============= TRUSTLESS GAME ACCOUNTS =============
+
+#[derive(Accounts)]
+pub struct InitBoard<'info> {
+    #[account(
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        init,
+        payer = player1,
+        space = 8 + ChessBoard::INIT_SPACE,
+        seeds = [b"board", game_match.game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub chess_board: Account<'info, ChessBoard>,
+    
+    #[account(mut)]
+    pub player1: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitMove<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"board", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub chess_board: Account<'info, ChessBoard>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub player: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ProveMove<'info> {
+    #[account(
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+}
+
+// ============= CHESS CLOCK ACCOUNTS =============
+
+#[derive(Accounts)]
+pub struct PressClock<'info> {
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Heartbeat<'info> {
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RecordPlayerActivity<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimFlag<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub claimer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// ============= PLAYER ENDINGS ACCOUNTS =============
+
+#[derive(Accounts)]
+pub struct PlayerEnding<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub player: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct AbortGame<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"game_config"], bump)]
+    pub game_config: Account<'info, GameConfig>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_counter", &(game_match.game_id % GAME_COUNTER_SHARDS).to_le_bytes()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        mut,
+        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == game_match.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == game_match.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == game_match.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    pub player: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct OfferDraw<'info> {
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_game_id: u64)]
+pub struct Rematch<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"blacklist"], bump)]
+    pub blacklist: Account<'info, Blacklist>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &previous_game.game_id.to_le_bytes()],
+        bump
+    )]
+    pub previous_game: Account<'info, GameMatch>,
+    
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + GameMatch::INIT_SPACE,
+        seeds = [b"game", new_game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + GameCounter::INIT_SPACE,
+        seeds = [b"game_counter", (new_game_id % GAME_COUNTER_SHARDS).to_le_bytes().as_ref()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
+    
+    #[account(
+        init_if_needed,
+        payer = player,
+        token::mint = mint,
+        token::authority = escrow_token_account,
+        token::token_program = token_program,
+        seeds = [b"game_escrow", new_game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = previous_game.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [b"wager_mint", mint.key().as_ref()],
+        bump,
+        constraint = wager_mint.enabled @ ErrorCode::MintNotAllowed
+    )]
+    pub wager_mint: Account<'info, WagerMint>,
+    
+    #[account(mut)]
+    pub player_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub player: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+// ============= MATCH SERIES ACCOUNTS =============
+
+#[derive(Accounts)]
+#[instruction(series_id: u64)]
+pub struct CreateSeries<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"blacklist"], bump)]
+    pub blacklist: Account<'info, Blacklist>,
+    
+    #[account(
+        init,
+        payer = player1,
+        space = 8 + MatchSeries::INIT_SPACE,
+        seeds = [b"series", series_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub match_series: Account<'info, MatchSeries>,
+    
+    #[account(
+        init,
+        payer = player1,
+        token::mint = mint,
+        token::authority = escrow_token_account,
+        token::token_program = token_program,
+        seeds = [b"series_escrow", series_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [b"wager_mint", mint.key().as_ref()],
+        bump,
+        constraint = wager_mint.enabled @ ErrorCode::MintNotAllowed
+    )]
+    pub wager_mint: Account<'info, WagerMint>,
+    
+    #[account(mut)]
+    pub player1: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct JoinSeries<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"blacklist"], bump)]
+    pub blacklist: Account<'info, Blacklist>,
+    
+    #[account(
+        mut,
+        seeds = [b"series", &match_series.series_id.to_le_bytes()],
+        bump
+    )]
+    pub match_series: Account<'info, MatchSeries>,
+    
+    #[account(
+        mut,
+        seeds = [b"series_escrow", &match_series.series_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub player2: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelSeries<'info> {
+    #[account(
+        mut,
+        seeds = [b"series", &match_series.series_id.to_le_bytes()],
+        bump
+    )]
+    pub match_series: Account<'info, MatchSeries>,
+    
+    #[account(
+        mut,
+        seeds = [b"series_escrow", &match_series.series_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == match_series.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub player1: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(game_id: u64)]
+pub struct StartSeriesGame<'info> {
+    #[account(
+        mut,
+        seeds = [b"series", &match_series.series_id.to_le_bytes()],
+        bump
+    )]
+    pub match_series: Account<'info, MatchSeries>,
+    
+    #[account(
+        init,
+        payer = player,
+        space = 8 + GameMatch::INIT_SPACE,
+        seeds = [b"game", game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(mut)]
+    pub player: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveSeriesGame<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"series", &match_series.series_id.to_le_bytes()],
+        bump
+    )]
+    pub match_series: Account<'info, MatchSeries>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"series_escrow", &match_series.series_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == match_series.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == match_series.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == match_series.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    pub authority: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct TimeoutSeries<'info> {
+    #[account(
+        mut,
+        seeds = [b"series", &match_series.series_id.to_le_bytes()],
+        bump
+    )]
+    pub match_series: Account<'info, MatchSeries>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(
+        mut,
+        seeds = [b"series_escrow", &match_series.series_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = player1_token_account.owner == match_series.player1 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player1_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = player2_token_account.owner == match_series.player2 @ ErrorCode::InvalidTokenAccount
+    )]
+    pub player2_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub player: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// ============= TOURNAMENT ACCOUNTS =============
+
+#[derive(Accounts)]
+#[instruction(tournament_id: u64)]
+pub struct CreateTournament<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        init,
+        payer = organizer,
+        space = 8 + Tournament::INIT_SPACE,
+        seeds = [b"tournament", tournament_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub tournament: Account<'info, Tournament>,
+    
+    #[account(
+        init,
+        payer = organizer,
+        token::mint = mint,
+        token::authority = escrow_token_account,
+        token::token_program = token_program,
+        seeds = [b"tournament_escrow", tournament_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [b"wager_mint", mint.key().as_ref()],
+        bump,
+        constraint = wager_mint.enabled @ ErrorCode::MintNotAllowed
+    )]
+    pub wager_mint: Account<'info, WagerMint>,
+    
+    #[account(mut)]
+    pub organizer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterTournament<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"blacklist"], bump)]
+    pub blacklist: Account<'info, Blacklist>,
+    
+    #[account(
+        mut,
+        seeds = [b"tournament", &tournament.tournament_id.to_le_bytes()],
+        bump
+    )]
+    pub tournament: Account<'info, Tournament>,
+    
+    #[account(
+        mut,
+        seeds = [b"tournament_escrow", &tournament.tournament_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub player_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub player: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct OrganizeTournament<'info> {
+    #[account(
+        mut,
+        seeds = [b"tournament", &tournament.tournament_id.to_le_bytes()],
+        bump
+    )]
+    pub tournament: Account<'info, Tournament>,
+    
+    pub organizer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(round: u8)]
+pub struct PairRound<'info> {
+    #[account(
+        mut,
+        seeds = [b"tournament", &tournament.tournament_id.to_le_bytes()],
+        bump
+    )]
+    pub tournament: Account<'info, Tournament>,
+    
+    #[account(
+        init,
+        payer = organizer,
+        space = 8 + TournamentRound::INIT_SPACE,
+        seeds = [b"tournament_round", tournament.tournament_id.to_le_bytes().as_ref(), round.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub tournament_round: Account<'info, TournamentRound>,
+    
+    #[account(mut)]
+    pub organizer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReportPairingResult<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"tournament", &tournament.tournament_id.to_le_bytes()],
+        bump
+    )]
+    pub tournament: Account<'info, Tournament>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"tournament_round",
+            &tournament.tournament_id.to_le_bytes(),
+            &[tournament_round.round]
+        ],
+        bump
+    )]
+    pub tournament_round: Account<'info, TournamentRound>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeTournament<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"tournament", &tournament.tournament_id.to_le_bytes()],
+        bump
+    )]
+    pub tournament: Account<'info, Tournament>,
+    
+    #[account(
+        mut,
+        seeds = [b"tournament_escrow", &tournament.tournament_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(constraint = fees.wager_mint.mint == tournament.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    pub signer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelTournament<'info> {
+    #[account(
+        mut,
+        seeds = [b"tournament", &tournament.tournament_id.to_le_bytes()],
+        bump
+    )]
+    pub tournament: Account<'info, Tournament>,
+    
+    #[account(
+        mut,
+        seeds = [b"tournament_escrow", &tournament.tournament_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub signer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// ============= ARENA ACCOUNTS =============
+
+#[derive(Accounts)]
+#[instruction(arena_id: u64)]
+pub struct CreateArena<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        init,
+        payer = organizer,
+        space = 8 + Arena::INIT_SPACE,
+        seeds = [b"arena", arena_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub arena: Account<'info, Arena>,
+    
+    #[account(
+        init,
+        payer = organizer,
+        token::mint = mint,
+        token::authority = escrow_token_account,
+        token::token_program = token_program,
+        seeds = [b"arena_escrow", arena_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [b"wager_mint", mint.key().as_ref()],
+        bump,
+        constraint = wager_mint.enabled @ ErrorCode::MintNotAllowed
+    )]
+    pub wager_mint: Account<'info, WagerMint>,
+    
+    #[account(mut)]
+    pub organizer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct JoinArena<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(seeds = [b"blacklist"], bump)]
+    pub blacklist: Account<'info, Blacklist>,
+    
+    #[account(
+        mut,
+        seeds = [b"arena", &arena.arena_id.to_le_bytes()],
+        bump
+    )]
+    pub arena: Account<'info, Arena>,
+    
+    #[account(
+        mut,
+        seeds = [b"arena_escrow", &arena.arena_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub player_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub player: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(game_id: u64)]
+pub struct StartArenaGame<'info> {
+    #[account(
+        mut,
+        seeds = [b"arena", &arena.arena_id.to_le_bytes()],
+        bump
+    )]
+    pub arena: Account<'info, Arena>,
+    
+    #[account(
+        init,
+        payer = organizer,
+        space = 8 + GameMatch::INIT_SPACE,
+        seeds = [b"game", game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(mut)]
+    pub organizer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveArenaGame<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"arena", &arena.arena_id.to_le_bytes()],
+        bump
+    )]
+    pub arena: Account<'info, Arena>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeArena<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"arena", &arena.arena_id.to_le_bytes()],
+        bump
+    )]
+    pub arena: Account<'info, Arena>,
+    
+    #[account(
+        mut,
+        seeds = [b"arena_escrow", &arena.arena_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(constraint = fees.wager_mint.mint == arena.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    pub signer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelArena<'info> {
+    #[account(
+        mut,
+        seeds = [b"arena", &arena.arena_id.to_le_bytes()],
+        bump
+    )]
+    pub arena: Account<'info, Arena>,
+    
+    #[account(
+        mut,
+        seeds = [b"arena_escrow", &arena.arena_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub organizer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// ============= SQUAD ACCOUNTS =============
+
+#[derive(Accounts)]
+#[instruction(squad_id: u64)]
+pub struct CreateSquad<'info> {
+    #[account(
+        init,
+        payer = leader,
+        space = 8 + Squad::INIT_SPACE,
+        seeds = [b"squad", squad_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub squad: Account<'info, Squad>,
+    
+    #[account(
+        init,
+        payer = leader,
+        token::mint = mint,
+        token::authority = vault_token_account,
+        token::token_program = token_program,
+        seeds = [b"squad_vault", squad_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [b"wager_mint", mint.key().as_ref()],
+        bump,
+        constraint = wager_mint.enabled @ ErrorCode::MintNotAllowed
+    )]
+    pub wager_mint: Account<'info, WagerMint>,
+    
+    #[account(mut)]
+    pub leader: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct JoinSquad<'info> {
+    #[account(
+        mut,
+        seeds = [b"squad", &squad.squad_id.to_le_bytes()],
+        bump
+    )]
+    pub squad: Account<'info, Squad>,
+    
+    pub leader: Signer<'info>,
+    
+    pub member: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LeaveSquad<'info> {
+    #[account(
+        mut,
+        seeds = [b"squad", &squad.squad_id.to_le_bytes()],
+        bump
+    )]
+    pub squad: Account<'info, Squad>,
+    
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FundSquadVault<'info> {
+    #[account(
+        seeds = [b"squad", &squad.squad_id.to_le_bytes()],
+        bump
+    )]
+    pub squad: Account<'info, Squad>,
+    
+    #[account(
+        mut,
+        seeds = [b"squad_vault", &squad.squad_id.to_le_bytes()],
+        bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = squad.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub funder: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct DistributeSquadVault<'info> {
+    #[account(
+        seeds = [b"squad", &squad.squad_id.to_le_bytes()],
+        bump
+    )]
+    pub squad: Account<'info, Squad>,
+    
+    #[account(
+        mut,
+        seeds = [b"squad_vault", &squad.squad_id.to_le_bytes()],
+        bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = squad.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = recipient_token_account.mint == squad.mint @ ErrorCode::InvalidTokenAccount
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub leader: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(battle_id: u64)]
+pub struct CreateTeamBattle<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        init,
+        payer = leader,
+        space = 8 + TeamBattle::INIT_SPACE,
+        seeds = [b"team_battle", battle_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub team_battle: Account<'info, TeamBattle>,
+    
+    #[account(
+        init,
+        payer = leader,
+        token::mint = mint,
+        token::authority = escrow_token_account,
+        token::token_program = token_program,
+        seeds = [b"team_battle_escrow", battle_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        seeds = [b"squad", &squad_a.squad_id.to_le_bytes()],
+        bump
+    )]
+    pub squad_a: Account<'info, Squad>,
+    
+    #[account(
+        mut,
+        seeds = [b"squad_vault", &squad_a.squad_id.to_le_bytes()],
+        bump
+    )]
+    pub squad_a_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        seeds = [b"squad", &squad_b.squad_id.to_le_bytes()],
+        bump
+    )]
+    pub squad_b: Account<'info, Squad>,
+    
+    #[account(address = squad_a.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [b"wager_mint", mint.key().as_ref()],
+        bump,
+        constraint = wager_mint.enabled @ ErrorCode::MintNotAllowed
+    )]
+    pub wager_mint: Account<'info, WagerMint>,
     
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub leader: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptTeamBattle<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"team_battle", &team_battle.battle_id.to_le_bytes()],
+        bump
+    )]
+    pub team_battle: Account<'info, TeamBattle>,
+    
+    #[account(
+        mut,
+        seeds = [b"team_battle_escrow", &team_battle.battle_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"squad", &team_battle.squad_a.to_le_bytes()],
+        bump
+    )]
+    pub squad_a: Account<'info, Squad>,
+    
+    #[account(
+        mut,
+        seeds = [b"squad", &team_battle.squad_b.to_le_bytes()],
+        bump
+    )]
+    pub squad_b: Account<'info, Squad>,
+    
+    #[account(
+        mut,
+        seeds = [b"squad_vault", &team_battle.squad_b.to_le_bytes()],
+        bump
+    )]
+    pub squad_b_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub leader: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelTeamBattle<'info> {
+    #[account(
+        mut,
+        seeds = [b"team_battle", &team_battle.battle_id.to_le_bytes()],
+        bump
+    )]
+    pub team_battle: Account<'info, TeamBattle>,
+    
+    #[account(
+        mut,
+        seeds = [b"team_battle_escrow", &team_battle.battle_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        seeds = [b"squad", &team_battle.squad_a.to_le_bytes()],
+        bump
+    )]
+    pub squad_a: Account<'info, Squad>,
+    
+    #[account(
+        mut,
+        seeds = [b"squad_vault", &team_battle.squad_a.to_le_bytes()],
+        bump
+    )]
+    pub squad_a_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub leader: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(game_id: u64)]
+pub struct StartBattleGame<'info> {
+    #[account(
+        mut,
+        seeds = [b"team_battle", &team_battle.battle_id.to_le_bytes()],
+        bump
+    )]
+    pub team_battle: Account<'info, TeamBattle>,
+    
+    #[account(
+        seeds = [b"squad", &team_battle.squad_a.to_le_bytes()],
+        bump
+    )]
+    pub squad_a: Account<'info, Squad>,
+    
+    #[account(
+        seeds = [b"squad", &team_battle.squad_b.to_le_bytes()],
+        bump
+    )]
+    pub squad_b: Account<'info, Squad>,
+    
+    #[account(
+        init,
+        payer = leader,
+        space = 8 + GameMatch::INIT_SPACE,
+        seeds = [b"game", game_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    #[account(mut)]
+    pub leader: Signer<'info>,
     
     pub system_program: Program<'info, System>,
 }
 
-#[derive(Accounts)]
-pub struct MintTokens<'info> {
-    #[account(mut, seeds = [b"token_state"], bump)]
-    pub token_state: Account<'info, TokenState>,
-    
-    #[account(seeds = [b"blacklist"], bump)]
-    pub blacklist: Account<'info, Blacklist>,
-    
-    #[account(mut)]
-    pub mint: Account<'info, Mint>,
-    
-    /// CHECK: Mint authority PDA
-    pub mint_authority: AccountInfo<'info>,
-    
-    /// CHECK: Recipient address
-    pub recipient: AccountInfo<'info>,
-    
-    #[account(mut)]
-    pub recipient_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    
-    pub token_program: Program<'info, Token>,
+#[derive(Accounts)]
+pub struct ResolveBattleGame<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"team_battle", &team_battle.battle_id.to_le_bytes()],
+        bump
+    )]
+    pub team_battle: Account<'info, TeamBattle>,
+    
+    #[account(
+        seeds = [b"squad", &team_battle.squad_a.to_le_bytes()],
+        bump
+    )]
+    pub squad_a: Account<'info, Squad>,
+    
+    #[account(
+        seeds = [b"squad", &team_battle.squad_b.to_le_bytes()],
+        bump
+    )]
+    pub squad_b: Account<'info, Squad>,
+    
+    #[account(
+        mut,
+        seeds = [b"game", &game_match.game_id.to_le_bytes()],
+        bump
+    )]
+    pub game_match: Account<'info, GameMatch>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeTeamBattle<'info> {
+    #[account(seeds = [b"token_state"], bump)]
+    pub token_state: Account<'info, TokenState>,
+    
+    #[account(
+        mut,
+        seeds = [b"team_battle", &team_battle.battle_id.to_le_bytes()],
+        bump
+    )]
+    pub team_battle: Account<'info, TeamBattle>,
+    
+    #[account(
+        mut,
+        seeds = [b"team_battle_escrow", &team_battle.battle_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    /// Mint del escrow: transferencias checked y quema de la parte del fee
+    #[account(mut, address = escrow_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"squad", &team_battle.squad_a.to_le_bytes()],
+        bump
+    )]
+    pub squad_a: Account<'info, Squad>,
+    
+    #[account(
+        mut,
+        seeds = [b"squad_vault", &team_battle.squad_a.to_le_bytes()],
+        bump
+    )]
+    pub squad_a_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"squad", &team_battle.squad_b.to_le_bytes()],
+        bump
+    )]
+    pub squad_b: Account<'info, Squad>,
+    
+    #[account(
+        mut,
+        seeds = [b"squad_vault", &team_battle.squad_b.to_le_bytes()],
+        bump
+    )]
+    pub squad_b_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(constraint = fees.wager_mint.mint == team_battle.mint @ ErrorCode::MintNotAllowed)]
+    pub fees: FeeAccountsCtx<'info>,
+    
+    pub signer: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// ============= STATE =============
+
+#[account]
+#[derive(InitSpace)]
+pub struct TokenState {
+    pub is_initialized: bool,
+    pub authority: Pubkey,
+    pub pending_authority: Option<Pubkey>,
+    pub max_supply: u64,
+    pub current_supply: u64,
+    pub daily_mint_limit: u64,
+    pub minted_today: u64,
+    pub last_mint_reset: i64,
+    pub swap_fee_basis_points: u16,
+    pub chess_to_game_rate: u64,
+    pub is_paused: bool,
+    pub created_at: i64,
+    pub treasury: Pubkey,
+    pub game_fee_basis_points: u16,
+    pub draw_fee_basis_points: u16,
+    #[max_len(MAX_FEE_TIERS)]
+    pub fee_tiers: Vec<FeeTier>,
+    pub fee_split: FeeSplit,
+    pub staking_pool: Pubkey,
+    pub version: u8,
+}
+
+// Layout original de TokenState (versión 0), solo para `migrate_token_state`
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct TokenStateV0 {
+    pub is_initialized: bool,
+    pub authority: Pubkey,
+    pub pending_authority: Option<Pubkey>,
+    pub max_supply: u64,
+    pub current_supply: u64,
+    pub daily_mint_limit: u64,
+    pub minted_today: u64,
+    pub last_mint_reset: i64,
+    pub swap_fee_basis_points: u16,
+    pub chess_to_game_rate: u64,
+    pub is_paused: bool,
+    pub created_at: i64,
+}
+
+// Reparto de cada fee entre destinos (basis points que suman 10000)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct FeeSplit {
+    pub treasury_bps: u16,
+    pub burn_bps: u16,
+    pub staking_bps: u16,
+    pub referrer_bps: u16,
+}
+
+// Cantidades repartidas de un fee concreto
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct FeeDistribution {
+    pub treasury: u64,
+    pub burned: u64,
+    pub staking: u64,
+    pub referrer: u64,
+}
+
+// Tramo de fee por tamaño de apuesta (ordenados por min_stake ascendente)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct FeeTier {
+    pub min_stake: u64,
+    pub fee_basis_points: u16,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Blacklist {
+    #[max_len(1000)]
+    pub addresses: Vec<Pubkey>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct SwapRecord {
+    pub user: Pubkey,
+    #[max_len(10)]
+    pub from_token: String,
+    #[max_len(10)]
+    pub to_token: String,
+    pub from_amount: u64,
+    pub to_amount: u64,
+    pub fee: u64,
+    pub timestamp: i64,
+    pub processed: bool,
+}
+
+// Game Match State
+#[account]
+#[derive(InitSpace)]
+pub struct GameMatch {
+    pub game_id: u64,
+    pub player1: Pubkey,
+    pub player2: Pubkey,
+    pub bet_amount: u64,
+    pub total_pot: u64,
+    pub time_control: TimeControl,
+    #[max_len(50)]
+    pub title: String,
+    pub status: GameStatus,
+    pub mode: GameMode,
+    pub winner: Option<Pubkey>,
+    pub created_at: i64,
+    pub started_at: i64,
+    pub ended_at: i64,
+    pub escrow_bump: u8,
+    pub player1_time_left: i64,
+    pub player2_time_left: i64,
+    pub player1_to_move: bool,
+    pub clock_running_since: i64,
+    pub player1_last_active: i64,
+    pub player2_last_active: i64,
+    pub record: Option<GameRecord>,
+    pub player1_result: Option<ResultClaim>,
+    pub player2_result: Option<ResultClaim>,
+    pub proposed_result: Option<ResultClaim>,
+    pub settlement_deadline: i64,
+    pub disputed_by: Option<Pubkey>,
+    pub dispute_bond: u64,
+    pub draw_offered_by: Option<Pubkey>,
+    pub moves_played: u16,
+    pub invited_opponent: Option<Pubkey>,
+    pub invite_hash: Option<[u8; 32]>,
+    pub expires_at: i64,
+    pub referrer: Option<Pubkey>,
+    pub mint: Pubkey,
+    pub player1_deposit: u64,
+    pub player2_deposit: u64,
+    pub player2_stake: u64,
+    pub series_id: Option<u64>,
+    pub previous_game: Option<u64>,
+    pub rematch_game_id: Option<u64>,
+    pub arena_id: Option<u64>,
+    pub team_battle_id: Option<u64>,
+    pub rent_payer: Pubkey, // quien pagó la renta de la partida (y de su escrow, si lo tiene)
+}
+
+// Serie de partidas entre dos jugadores con un único escrow
+#[account]
+#[derive(InitSpace)]
+pub struct MatchSeries {
+    pub series_id: u64,
+    pub player1: Pubkey,
+    pub player2: Pubkey,
+    pub mint: Pubkey,
+    pub stake: u64,
+    pub player1_deposit: u64,
+    pub player2_deposit: u64,
+    pub total_pot: u64,
+    pub format: SeriesFormat,
+    pub games_planned: u8,
+    pub games_played: u8,
+    pub player1_points: u16, // medios puntos: victoria = 2, tablas = 1
+    pub player2_points: u16,
+    pub time_control: TimeControl,
+    pub current_game_id: Option<u64>,
+    pub status: GameStatus,
+    pub winner: Option<Pubkey>,
+    pub invited_opponent: Option<Pubkey>,
+    pub created_at: i64,
+    pub started_at: i64,
+    pub ended_at: i64,
+    pub escrow_bump: u8,
+}
+
+// Formato de una serie
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum SeriesFormat {
+    BestOf,     // Al mejor de N: termina en cuanto está decidida
+    FixedGames, // N partidas por puntos
+}
+
+// Torneo con inscripción en escrow y tabla de premios
+#[account]
+#[derive(InitSpace)]
+pub struct Tournament {
+    pub tournament_id: u64,
+    pub organizer: Pubkey,
+    pub format: TournamentFormat,
+    pub mint: Pubkey,
+    pub entry_fee: u64,
+    pub max_players: u8,
+    #[max_len(MAX_TOURNAMENT_PLAYERS)]
+    pub players: Vec<TournamentPlayer>,
+    #[max_len(MAX_PRIZE_PLACES)]
+    pub prize_table: Vec<u16>,
+    pub time_control: TimeControl,
+    pub total_pot: u64,
+    pub total_rounds: u8,
+    pub current_round: u8,
+    pub round_in_progress: bool,
+    pub pending_results: u8,
+    pub status: TournamentStatus,
+    pub registration_deadline: i64,
+    pub created_at: i64,
+    pub started_at: i64,
+    pub ended_at: i64,
+    pub round_deadline: i64, // Límite para emparejar o cerrar la ronda en curso
+    pub escrow_bump: u8,
+}
+
+// Emparejamientos de una ronda de torneo
+#[account]
+#[derive(InitSpace)]
+pub struct TournamentRound {
+    pub tournament_id: u64,
+    pub round: u8,
+    #[max_len(MAX_PAIRINGS)]
+    pub pairings: Vec<Pairing>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct TournamentPlayer {
+    pub player: Pubkey,
+    pub deposit: u64,
+    pub score: u16, // medios puntos: victoria o bye = 2, tablas = 1
+    pub eliminated: bool,
+}
+
+// Emparejamiento propuesto por el organizador (índices en `players`)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct PairingInput {
+    pub white: u8,
+    pub black: u8,
+    pub game_id: Option<u64>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct Pairing {
+    pub white: u8,
+    pub black: u8,
+    pub game_id: Option<u64>,
+    pub result: PairingResult,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum PairingResult {
+    Pending,
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Bye,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum TournamentFormat {
+    Knockout, // Eliminatoria: el perdedor queda fuera
+    Swiss,    // Suizo: rondas fijas por puntos
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum TournamentStatus {
+    Registration, // Inscripción abierta
+    Running,      // En juego
+    Completed,    // Premios pagados
+    Cancelled,    // Cancelado, inscripciones devueltas
+}
+
+// Arena por tiempo con clasificación por puntos
+#[account]
+#[derive(InitSpace)]
+pub struct Arena {
+    pub arena_id: u64,
+    pub organizer: Pubkey,
+    pub mint: Pubkey,
+    pub entry_fee: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub max_players: u8,
+    #[max_len(MAX_ARENA_PLAYERS)]
+    pub players: Vec<ArenaPlayer>,
+    #[max_len(MAX_PRIZE_PLACES)]
+    pub prize_table: Vec<u16>,
+    pub time_control: TimeControl,
+    pub total_pot: u64,
+    pub games_in_progress: u8,
+    pub status: ArenaStatus,
+    pub created_at: i64,
+    pub ended_at: i64,
+    pub escrow_bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct ArenaPlayer {
+    pub player: Pubkey,
+    pub deposit: u64,
+    pub points: u32,
+    pub streak: u8, // victorias seguidas
+    pub games_played: u16,
+    pub in_game: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ArenaStatus {
+    Open,      // Inscripción abierta / en juego hasta end_time
+    Completed, // Premios pagados
+    Cancelled, // Cancelada, inscripciones devueltas
+}
+
+// Squad con líder, miembros y vault de tokens
+#[account]
+#[derive(InitSpace)]
+pub struct Squad {
+    pub squad_id: u64,
+    pub leader: Pubkey,
+    #[max_len(32)]
+    pub name: String,
+    pub mint: Pubkey,
+    #[max_len(MAX_SQUAD_MEMBERS)]
+    pub members: Vec<Pubkey>,
+    pub active_battles: u8,
+    pub created_at: i64,
+    pub vault_bump: u8,
+}
+
+// Batalla por equipos entre dos squads con stakes desde sus vaults
+#[account]
+#[derive(InitSpace)]
+pub struct TeamBattle {
+    pub battle_id: u64,
+    pub squad_a: u64, // squad retador
+    pub squad_b: u64,
+    pub mint: Pubkey,
+    pub stake: u64,
+    pub squad_a_deposit: u64,
+    pub squad_b_deposit: u64,
+    pub total_pot: u64,
+    pub squad_a_score: u32, // medios puntos
+    pub squad_b_score: u32,
+    pub games_played: u16,
+    pub games_in_progress: u8,
+    pub time_control: TimeControl,
+    pub end_time: i64,
+    pub status: GameStatus,
+    pub winner_squad: Option<u64>,
+    pub created_at: i64,
+    pub started_at: i64,
+    pub ended_at: i64,
+    pub escrow_bump: u8,
+}
+
+// Control de tiempo estructurado (segundos)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct TimeControl {
+    pub base_seconds: u32,
+    pub increment_seconds: u16,
+    pub delay_seconds: u16,
+}
+
+// Modo de partida
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum GameMode {
+    Standard,   // Resultado reportado por authority, oráculos o jugadores
+    Trustless,  // Jugadas validadas on-chain
+}
+
+// Tablero de una partida trustless
+#[account]
+#[derive(InitSpace)]
+pub struct ChessBoard {
+    pub game_id: u64,
+    pub board: [u8; 64],
+    pub white_to_move: bool,
+    pub castling: u8,
+    pub en_passant: u8,
+    pub halfmove_clock: u16,
+    pub fullmove_number: u16,
+    pub ply_count: u16,
+    // Hashes de posiciones desde la última jugada irreversible (triple repetición)
+    #[max_len(101)]
+    pub position_history: Vec<u64>,
+}
+
+// Compromiso sobre el registro de la partida
+// moves_root: raíz Merkle de las jugadas (ver move_leaf), final_fen_hash: sha256 del FEN final
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct GameRecord {
+    pub moves_root: [u8; 32],
+    pub move_count: u16,
+    pub final_fen_hash: [u8; 32],
+}
+
+// Hoja del registro de jugadas con su prueba de inclusión (ver move_leaf)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MoveLeafProof {
+    pub ply: u16,
+    pub position_before: Vec<u8>,
+    pub from: u8,
+    pub to: u8,
+    pub promotion: u8,
+    pub proof: Vec<[u8; 32]>,
+}
+
+// Prueba de fraude contra un registro comprometido
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub enum FraudProof {
+    IllegalMove(MoveLeafProof),                 // jugada ilegal en su posición
+    WrongStartPosition(MoveLeafProof),          // la hoja 0 no parte de la posición inicial
+    BrokenChain(MoveLeafProof, MoveLeafProof),  // hojas k y k+1 que no encadenan
+}
+
+// Resultado declarado por un jugador
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct ResultClaim {
+    pub winner: Option<Pubkey>,
+    pub is_draw: bool,
+}
+
+// Registro de oráculos de resultados
+#[account]
+#[derive(InitSpace)]
+pub struct OracleRegistry {
+    pub admin: Pubkey,
+    #[max_len(10)]
+    pub oracles: Vec<Pubkey>,
+    pub quorum_bet_threshold: u64,
+    pub quorum_size: u8,
+}
+
+// Votos de oráculos acumulados para una partida de apuesta alta
+#[account]
+#[derive(InitSpace)]
+pub struct PendingResult {
+    pub game_id: u64,
+    #[max_len(10)]
+    pub votes: Vec<OracleVote>,
+    pub created_at: i64,
+    pub payer: Pubkey, // relayer del primer voto: recupera la renta al alcanzarse el quórum
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct OracleVote {
+    pub oracle: Pubkey,
+    pub winner: Option<Pubkey>,
+    pub is_draw: bool,
+    pub record: Option<GameRecord>,
+}
+
+impl GameMatch {
+    /// Apuesta mayor de la partida, usada para elegir el tramo de fee
+    pub fn largest_stake(&self) -> u64 {
+        self.bet_amount.max(self.player2_stake)
+    }
+}
+
+impl OracleVote {
+    /// Dos votos coinciden si declaran el mismo resultado y el mismo registro
+    pub fn agrees_with(&self, other: &OracleVote) -> bool {
+        self.winner == other.winner && self.is_draw == other.is_draw && self.record == other.record
+    }
+}
+
+// Configuración de partidas (liquidación optimista y abandono)
+#[account]
+#[derive(InitSpace)]
+pub struct GameConfig {
+    pub challenge_window_seconds: i64,
+    pub dispute_bond: u64,
+    #[max_len(5)]
+    pub arbiters: Vec<Pubkey>,
+    pub abandonment_min_seconds: i64,
+    pub abandonment_percent: u16,
+    pub abort_window_seconds: i64,
+    pub expiry_bounty_lamports: u64,
+    pub optimistic_settlement: bool, // si está activa, resolve_game queda deshabilitado
+    pub dispute_timeout_seconds: i64, // plazo del árbitro antes del reembolso sin fallo
+}
+
+// Mint admitido para apuestas, con límites y destinos de fees propios
+#[account]
+#[derive(InitSpace)]
+pub struct WagerMint {
+    pub mint: Pubkey,
+    pub treasury: Pubkey,
+    pub staking_pool: Pubkey,
+    pub min_stake: u64,
+    pub max_stake: u64,
+    pub enabled: bool,
+}
+
+// Game Counter (uno por shard; los totales globales se suman off-chain)
+#[account]
+#[derive(InitSpace)]
+pub struct GameCounter {
+    pub total_games: u64,
+    pub active_games: u64,
+}
+
+// Game Status Enum
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum GameStatus {
+    Waiting,    // Esperando oponente
+    Active,     // Partida en curso
+    Completed,  // Terminada con ganador
+    Draw,       // Empate
+    Cancelled,  // Cancelada antes de empezar
+    Timeout,    // Terminada por timeout
+    PendingSettlement, // Resultado propuesto, en ventana de disputa
+    Disputed,   // Resultado disputado, pendiente de árbitro
+    Aborted,    // Abortada al inicio, apuestas devueltas sin fee
+}
+
+// ============= EVENTS =============
+
+#[event]
+pub struct ProgramInitialized {
+    pub authority: Pubkey,
+    pub max_supply: u64,
+    pub daily_mint_limit: u64,
+    pub swap_fee_basis_points: u16,
+    pub chess_to_game_rate: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TokensMinted {
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub new_total_supply: u64,
+    pub minted_today: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TokensBurned {
+    pub burner: Pubkey,
+    pub amount: u64,
+    pub new_total_supply: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SwapExecuted {
+    pub user: Pubkey,
+    pub from_token: String,
+    pub from_amount: u64,
+    pub to_token: String,
+    pub to_amount: u64,
+    pub fee: u64,
+    pub fee_distribution: FeeDistribution,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmergencyPause {
+    pub admin: Pubkey,
+    pub reason: String,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProgramUnpaused {
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AddressBlacklisted {
+    pub address: Pubkey,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AddressRemovedFromBlacklist {
+    pub address: Pubkey,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityTransferInitiated {
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConfigUpdated {
+    pub daily_mint_limit: u64,
+    pub swap_fee_basis_points: u16,
+    pub treasury: Pubkey,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GameFeesUpdated {
+    pub game_fee_basis_points: u16,
+    pub draw_fee_basis_points: u16,
+    pub fee_tiers: Vec<FeeTier>,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeSplitUpdated {
+    pub split: FeeSplit,
+    pub staking_pool: Pubkey,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WagerMintConfigured {
+    pub mint: Pubkey,
+    pub treasury: Pubkey,
+    pub staking_pool: Pubkey,
+    pub min_stake: u64,
+    pub max_stake: u64,
+    pub enabled: bool,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TokenStateMigrated {
+    pub from_version: u8,
+    pub to_version: u8,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+// Game Escrow Events
+#[event]
+pub struct GameCreated {
+    pub game_id: u64,
+    pub player1: Pubkey,
+    pub mint: Pubkey,
+    pub bet_amount: u64,
+    pub player2_stake: u64,
+    pub time_control: TimeControl,
+    pub title: String,
+    pub mode: GameMode,
+    pub invited_opponent: Option<Pubkey>,
+    pub is_private: bool,
+    pub expires_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GameJoined {
+    pub game_id: u64,
+    pub player1: Pubkey,
+    pub player2: Pubkey,
+    pub total_pot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GameResolved {
+    pub game_id: u64,
+    pub winner: Option<Pubkey>,
+    pub is_draw: bool,
+    pub prize_amount: u64,
+    pub dao_fee: u64,
+    pub fee_basis_points: u16,
+    pub fee_distribution: FeeDistribution,
+    pub record: Option<GameRecord>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GameCancelled {
+    pub game_id: u64,
+    pub player1: Pubkey,
+    pub refund_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GameArchived {
+    pub game_id: u64,
+    pub player1: Pubkey,
+    pub player2: Pubkey,
+    pub bet_amount: u64,
+    pub total_pot: u64,
+    pub status: GameStatus,
+    pub mode: GameMode,
+    pub winner: Option<Pubkey>,
+    pub record: Option<GameRecord>,
+    pub created_at: i64,
+    pub ended_at: i64,
+    pub escrow_dust: u64,
+    pub rent_reclaimed: u64,
+    pub closed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GameExpired {
+    pub game_id: u64,
+    pub player1: Pubkey,
+    pub refund_amount: u64,
+    pub player2_refund: u64,
+    pub cranker: Pubkey,
+    pub bounty: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GameTimeout {
+    pub game_id: u64,
+    pub claimer: Pubkey,
+    pub prize_amount: u64,
+    pub dao_fee: u64,
+    pub fee_basis_points: u16,
+    pub fee_distribution: FeeDistribution,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ResultSubmitted {
+    pub game_id: u64,
+    pub player: Pubkey,
+    pub winner: Option<Pubkey>,
+    pub is_draw: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ResultDisputed {
+    pub game_id: u64,
+    pub player1_winner: Option<Pubkey>,
+    pub player1_is_draw: bool,
+    pub player2_winner: Option<Pubkey>,
+    pub player2_is_draw: bool,
+    pub timestamp: i64,
+}
+
+// Oracle Events
+#[event]
+pub struct OracleRegistryInitialized {
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OracleAdded {
+    pub oracle: Pubkey,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OracleRotated {
+    pub old_oracle: Pubkey,
+    pub new_oracle: Pubkey,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OracleRevoked {
+    pub oracle: Pubkey,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OracleQuorumUpdated {
+    pub quorum_bet_threshold: u64,
+    pub quorum_size: u8,
+    pub total_oracles: u8,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OracleVoteSubmitted {
+    pub game_id: u64,
+    pub oracle: Pubkey,
+    pub winner: Option<Pubkey>,
+    pub is_draw: bool,
+    pub matching_votes: u8,
+    pub quorum_size: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OracleDisagreement {
+    pub game_id: u64,
+    pub oracle: Pubkey,
+    pub winner: Option<Pubkey>,
+    pub is_draw: bool,
+    pub total_votes: u8,
+    pub timestamp: i64,
+}
+
+// Optimistic Settlement Events
+#[event]
+pub struct GameConfigUpdated {
+    pub challenge_window_seconds: i64,
+    pub dispute_bond: u64,
+    pub arbiters: Vec<Pubkey>,
+    pub abandonment_min_seconds: i64,
+    pub abandonment_percent: u16,
+    pub abort_window_seconds: i64,
+    pub expiry_bounty_lamports: u64,
+    pub optimistic_settlement: bool,
+    pub dispute_timeout_seconds: i64,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ResultProposed {
+    pub game_id: u64,
+    pub winner: Option<Pubkey>,
+    pub is_draw: bool,
+    pub record: Option<GameRecord>,
+    pub settlement_deadline: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DisputeOpened {
+    pub game_id: u64,
+    pub disputed_by: Pubkey,
+    pub bond: u64,
+    pub ruling_deadline: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DisputeExpired {
+    pub game_id: u64,
+    pub disputed_by: Pubkey,
+    pub player1_refund: u64,
+    pub player2_refund: u64,
+    pub bond: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DisputeResolved {
+    pub game_id: u64,
+    pub arbiter: Pubkey,
+    pub disputed_by: Pubkey,
+    pub dispute_upheld: bool,
+    pub bond: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FraudProven {
+    pub game_id: u64,
+    pub ply: u16,
+    pub offender: Pubkey,
+    pub prover: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct InvalidRecordProven {
+    pub game_id: u64,
+    pub prover: Pubkey,
+    pub player1_refund: u64,
+    pub player2_refund: u64,
+    pub timestamp: i64,
+}
+
+// Trustless Game Events
+#[event]
+pub struct MovePlayed {
+    pub game_id: u64,
+    pub player: Pubkey,
+    pub from: u8,
+    pub to: u8,
+    pub promotion: u8,
+    pub ply: u16,
+    pub in_check: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MoveProven {
+    pub game_id: u64,
+    pub ply: u16,
+    pub from: u8,
+    pub to: u8,
+    pub promotion: u8,
+    pub is_legal: bool,
+    pub timestamp: i64,
+}
+
+// Chess Clock Events
+#[event]
+pub struct ClockPressed {
+    pub game_id: u64,
+    pub player: Pubkey,
+    pub player1_time_left: i64,
+    pub player2_time_left: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GameAborted {
+    pub game_id: u64,
+    pub aborted_by: Pubkey,
+    pub player1_refund: u64,
+    pub player2_refund: u64,
+    pub moves_played: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RematchCreated {
+    pub game_id: u64,
+    pub previous_game: u64,
+    pub white: Pubkey,
+    pub black: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeriesCreated {
+    pub series_id: u64,
+    pub player1: Pubkey,
+    pub mint: Pubkey,
+    pub stake: u64,
+    pub format: SeriesFormat,
+    pub games_planned: u8,
+    pub time_control: TimeControl,
+    pub invited_opponent: Option<Pubkey>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeriesJoined {
+    pub series_id: u64,
+    pub player1: Pubkey,
+    pub player2: Pubkey,
+    pub total_pot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeriesCancelled {
+    pub series_id: u64,
+    pub player1: Pubkey,
+    pub refund_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeriesTimedOut {
+    pub series_id: u64,
+    pub game_id: u64,
+    pub player1_refund: u64,
+    pub player2_refund: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeriesGameStarted {
+    pub series_id: u64,
+    pub game_id: u64,
+    pub game_number: u8,
+    pub white: Pubkey,
+    pub black: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeriesScoreUpdated {
+    pub series_id: u64,
+    pub game_id: u64,
+    pub games_played: u8,
+    pub player1_points: u16,
+    pub player2_points: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeriesCompleted {
+    pub series_id: u64,
+    pub winner: Option<Pubkey>,
+    pub is_draw: bool,
+    pub player1_points: u16,
+    pub player2_points: u16,
+    pub player1_prize: u64,
+    pub player2_prize: u64,
+    pub dao_fee: u64,
+    pub fee_basis_points: u16,
+    pub fee_distribution: FeeDistribution,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TournamentCreated {
+    pub tournament_id: u64,
+    pub organizer: Pubkey,
+    pub format: TournamentFormat,
+    pub mint: Pubkey,
+    pub entry_fee: u64,
+    pub max_players: u8,
+    pub prize_table: Vec<u16>,
+    pub registration_deadline: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TournamentPlayerRegistered {
+    pub tournament_id: u64,
+    pub player: Pubkey,
+    pub player_index: u8,
+    pub total_pot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TournamentStarted {
+    pub tournament_id: u64,
+    pub players: u8,
+    pub total_rounds: u8,
+    pub total_pot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RoundPaired {
+    pub tournament_id: u64,
+    pub round: u8,
+    pub pairings: Vec<Pairing>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PairingResultReported {
+    pub tournament_id: u64,
+    pub round: u8,
+    pub pairing_index: u8,
+    pub white: Pubkey,
+    pub black: Pubkey,
+    pub result: PairingResult,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RoundCompleted {
+    pub tournament_id: u64,
+    pub round: u8,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct BurnTokens<'info> {
-    #[account(mut, seeds = [b"token_state"], bump)]
-    pub token_state: Account<'info, TokenState>,
-    
-    #[account(mut)]
-    pub mint: Account<'info, Mint>,
-    
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub user: Signer<'info>,
-    
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct TournamentCompleted {
+    pub tournament_id: u64,
+    pub winners: Vec<Pubkey>,
+    pub prizes: Vec<u64>,
+    pub dao_fee: u64,
+    pub fee_basis_points: u16,
+    pub fee_distribution: FeeDistribution,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct SwapChessToGame<'info> {
-    #[account(seeds = [b"token_state"], bump)]
-    pub token_state: Account<'info, TokenState>,
-    
-    #[account(seeds = [b"blacklist"], bump)]
-    pub blacklist: Account<'info, Blacklist>,
-    
-    #[account(
-        init,
-        payer = user,
-        space = 8 + SwapRecord::INIT_SPACE,
-        seeds = [b"swap", user.key().as_ref(), &Clock::get().unwrap().unix_timestamp.to_le_bytes()],
-        bump
-    )]
-    pub swap_record: Account<'info, SwapRecord>,
-    
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub treasury_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub user: Signer<'info>,
-    
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct TournamentCancelled {
+    pub tournament_id: u64,
+    pub refunded_players: u8,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct AdminAction<'info> {
-    #[account(mut, seeds = [b"token_state"], bump)]
-    pub token_state: Account<'info, TokenState>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
+#[event]
+pub struct ArenaCreated {
+    pub arena_id: u64,
+    pub organizer: Pubkey,
+    pub mint: Pubkey,
+    pub entry_fee: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub max_players: u8,
+    pub prize_table: Vec<u16>,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct ManageBlacklist<'info> {
-    #[account(seeds = [b"token_state"], bump)]
-    pub token_state: Account<'info, TokenState>,
-    
-    #[account(mut, seeds = [b"blacklist"], bump)]
-    pub blacklist: Account<'info, Blacklist>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
+#[event]
+pub struct ArenaJoined {
+    pub arena_id: u64,
+    pub player: Pubkey,
+    pub total_pot: u64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct TransferAuthority<'info> {
-    #[account(mut, seeds = [b"token_state"], bump)]
-    pub token_state: Account<'info, TokenState>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
+#[event]
+pub struct ArenaGameStarted {
+    pub arena_id: u64,
+    pub game_id: u64,
+    pub white: Pubkey,
+    pub black: Pubkey,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct AcceptAuthority<'info> {
-    #[account(mut, seeds = [b"token_state"], bump)]
-    pub token_state: Account<'info, TokenState>,
-    
-    #[account(mut)]
-    pub new_authority: Signer<'info>,
+#[event]
+pub struct ArenaScoreUpdated {
+    pub arena_id: u64,
+    pub game_id: u64,
+    pub white: Pubkey,
+    pub black: Pubkey,
+    pub white_points: u32,
+    pub black_points: u32,
+    pub timestamp: i64,
 }
 
-// ============= GAME ESCROW ACCOUNTS =============
+#[event]
+pub struct ArenaCompleted {
+    pub arena_id: u64,
+    pub winners: Vec<Pubkey>,
+    pub prizes: Vec<u64>,
+    pub dao_fee: u64,
+    pub fee_basis_points: u16,
+    pub fee_distribution: FeeDistribution,
+    pub timestamp: i64,
+}
 
-#[derive(Accounts)]
-#[instruction(bet_amount: u64, time_control: String, game_title: String)]
-pub struct CreateGame<'info> {
-    #[account(seeds = [b"token_state"], bump)]
-    pub token_state: Account<'info, TokenState>,
-    
-    #[account(seeds = [b"blacklist"], bump)]
-    pub blacklist: Account<'info, Blacklist>,
-    
-    #[account(
-        init,
-        payer = player1,
-        space = 8 + GameMatch::INIT_SPACE,
-        seeds = [b"game", &game_counter.total_games.to_le_bytes()],
-        bump
-    )]
-    pub game_match: Account<'info, GameMatch>,
-    
-    #[account(
-        init_if_needed,
-        payer = player1,
-        space = 8 + GameCounter::INIT_SPACE,
-        seeds = [b"game_counter"],
-        bump
-    )]
-    pub game_counter: Account<'info, GameCounter>,
-    
-    #[account(
-        init,
-        payer = player1,
-        token::mint = mint,
-        token::authority = escrow_token_account,
-        seeds = [b"game_escrow", &game_counter.total_games.to_le_bytes()],
-        bump
-    )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub player1_token_account: Account<'info, TokenAccount>,
-    
-    pub mint: Account<'info, Mint>,
-    
-    #[account(mut)]
-    pub player1: Signer<'info>,
-    
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
+#[event]
+pub struct ArenaCancelled {
+    pub arena_id: u64,
+    pub refunded_players: u8,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct JoinGame<'info> {
-    #[account(seeds = [b"token_state"], bump)]
-    pub token_state: Account<'info, TokenState>,
-    
-    #[account(seeds = [b"blacklist"], bump)]
-    pub blacklist: Account<'info, Blacklist>,
-    
-    #[account(
-        mut,
-        seeds = [b"game", &game_match.game_id.to_le_bytes()],
-        bump
-    )]
-    pub game_match: Account<'info, GameMatch>,
-    
-    #[account(
-        mut,
-        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
-        bump
-    )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub player2_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub player2: Signer<'info>,
-    
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct SquadCreated {
+    pub squad_id: u64,
+    pub leader: Pubkey,
+    pub name: String,
+    pub mint: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SquadMemberJoined {
+    pub squad_id: u64,
+    pub member: Pubkey,
+    pub members: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SquadMemberLeft {
+    pub squad_id: u64,
+    pub member: Pubkey,
+    pub removed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SquadVaultFunded {
+    pub squad_id: u64,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub vault_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SquadVaultDistributed {
+    pub squad_id: u64,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TeamBattleCreated {
+    pub battle_id: u64,
+    pub squad_a: u64,
+    pub squad_b: u64,
+    pub mint: Pubkey,
+    pub stake: u64,
+    pub end_time: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TeamBattleAccepted {
+    pub battle_id: u64,
+    pub squad_b: u64,
+    pub total_pot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TeamBattleCancelled {
+    pub battle_id: u64,
+    pub squad_a: u64,
+    pub refund_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TeamBattleRefunded {
+    pub battle_id: u64,
+    pub squad_a_refund: u64,
+    pub squad_b_refund: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BattleGameStarted {
+    pub battle_id: u64,
+    pub game_id: u64,
+    pub white: Pubkey,
+    pub black: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TeamBattleScoreUpdated {
+    pub battle_id: u64,
+    pub game_id: u64,
+    pub games_played: u16,
+    pub squad_a_score: u32,
+    pub squad_b_score: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TeamBattleCompleted {
+    pub battle_id: u64,
+    pub winner_squad: Option<u64>,
+    pub is_draw: bool,
+    pub squad_a_score: u32,
+    pub squad_b_score: u32,
+    pub squad_a_prize: u64,
+    pub squad_b_prize: u64,
+    pub dao_fee: u64,
+    pub fee_basis_points: u16,
+    pub fee_distribution: FeeDistribution,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DrawOffered {
+    pub game_id: u64,
+    pub offered_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PlayerActivity {
+    pub game_id: u64,
+    pub player: Pubkey,
+    pub reported_by: Pubkey,
+    pub timestamp: i64,
+}
+
+// ============= HELPERS =============
+
+/// Máximo de oráculos en el registro (debe coincidir con max_len de OracleRegistry)
+const MAX_ORACLES: usize = 10;
+
+/// Máximo de árbitros de disputas (debe coincidir con max_len de GameConfig)
+const MAX_ARBITERS: usize = 5;
+
+/// Prefijo de dominio para mensajes firmados por oráculos
+const ORACLE_RESULT_DOMAIN: &[u8] = b"chessdao:game_result";
+
+/// Prefijo de dominio para las hojas del árbol de jugadas
+const MOVE_LEAF_DOMAIN: &[u8] = b"chessdao:move";
+
+/// Profundidad máxima de una prueba Merkle de jugadas (2^16 hojas)
+const MAX_MOVE_PROOF_DEPTH: usize = 16;
+
+fn is_blacklisted(blacklist: &Account<Blacklist>, address: &Pubkey) -> bool {
+    blacklist.addresses.contains(address)
 }
 
-#[derive(Accounts)]
-pub struct ResolveGame<'info> {
-    #[account(seeds = [b"token_state"], bump)]
-    pub token_state: Account<'info, TokenState>,
-    
-    #[account(
-        mut,
-        seeds = [b"game", &game_match.game_id.to_le_bytes()],
-        bump
-    )]
-    pub game_match: Account<'info, GameMatch>,
+/// Indica si la partida requiere quórum de oráculos por su apuesta
+fn requires_oracle_quorum(registry: &OracleRegistry, game: &GameMatch) -> bool {
+    registry.quorum_bet_threshold > 0 && game.bet_amount >= registry.quorum_bet_threshold
+}
+
+/// Mensaje que firma un oráculo para un resultado:
+/// dominio || program_id || game_id || winner || is_draw || expiry || registro opcional
+/// (0, o 1 || moves_root || move_count || final_fen_hash)
+fn oracle_result_message(
+    game_id: u64,
+    winner_key: Pubkey,
+    is_draw: bool,
+    expiry: i64,
+    record: &Option<GameRecord>,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(ORACLE_RESULT_DOMAIN.len() + 32 + 8 + 32 + 1 + 8 + 67);
+    message.extend_from_slice(ORACLE_RESULT_DOMAIN);
+    message.extend_from_slice(crate::ID.as_ref());
+    message.extend_from_slice(&game_id.to_le_bytes());
+    message.extend_from_slice(winner_key.as_ref());
+    message.push(is_draw as u8);
+    message.extend_from_slice(&expiry.to_le_bytes());
+    match record {
+        Some(record) => {
+            message.push(1);
+            message.extend_from_slice(&record.moves_root);
+            message.extend_from_slice(&record.move_count.to_le_bytes());
+            message.extend_from_slice(&record.final_fen_hash);
+        }
+        None => message.push(0),
+    }
+    message
+}
+
+/// Hoja del árbol Merkle de jugadas:
+/// sha256(dominio || ply || posición antes de la jugada || from || to || promotion)
+fn move_leaf(ply: u16, position: &Position, mv: &ChessMove) -> [u8; 32] {
+    hashv(&[
+        MOVE_LEAF_DOMAIN,
+        &ply.to_le_bytes(),
+        &position.to_bytes(),
+        &[mv.from, mv.to, mv.promotion],
+    ]).to_bytes()
+}
+
+/// Verificar que una hoja está en el registro y devolver su posición y jugada
+fn verify_move_leaf(record: &GameRecord, leaf: &MoveLeafProof) -> Result<(Position, ChessMove)> {
+    require!(leaf.ply < record.move_count, ErrorCode::InvalidMoveProof);
+    require!(leaf.proof.len() <= MAX_MOVE_PROOF_DEPTH, ErrorCode::InvalidMoveProof);
     
-    #[account(mut, seeds = [b"game_counter"], bump)]
-    pub game_counter: Account<'info, GameCounter>,
+    let position = Position::from_bytes(&leaf.position_before).ok_or(ErrorCode::InvalidPosition)?;
+    let mv = ChessMove { from: leaf.from, to: leaf.to, promotion: leaf.promotion };
+    require!(
+        verify_merkle_proof(&record.moves_root, move_leaf(leaf.ply, &position, &mv), leaf.ply, &leaf.proof),
+        ErrorCode::InvalidMoveProof
+    );
+    Ok((position, mv))
+}
+
+/// Verificar una prueba Merkle de inclusión de una hoja en `root`
+/// Nodo interno = sha256(0x01 || izquierdo || derecho); en niveles impares
+/// el último nodo se empareja consigo mismo
+fn verify_merkle_proof(root: &[u8; 32], leaf: [u8; 32], index: u16, proof: &[[u8; 32]]) -> bool {
+    let mut node = leaf;
+    let mut index = index as u32;
+    for sibling in proof {
+        node = if index % 2 == 1 {
+            hashv(&[&[1u8], sibling, &node]).to_bytes()
+        } else {
+            hashv(&[&[1u8], &node, sibling]).to_bytes()
+        };
+        index /= 2;
+    }
+    index == 0 && &node == root
+}
+
+/// Verificar que la instrucción anterior es una verificación Ed25519
+/// de `message` firmada por `signer`
+fn verify_ed25519_signature(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    require!(current_index > 0, ErrorCode::MissingSignatureInstruction);
     
-    #[account(
-        mut,
-        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
-        bump
-    )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
+    let ix = load_instruction_at_checked((current_index - 1) as usize, instructions_sysvar)?;
+    require!(ix.program_id == ed25519_program::ID, ErrorCode::MissingSignatureInstruction);
     
-    #[account(mut)]
-    pub player1_token_account: Account<'info, TokenAccount>,
+    // Formato: [num_signatures: u8, padding: u8, offsets (7 x u16), datos...]
+    let data = &ix.data;
+    require!(data.len() >= 16 && data[0] == 1, ErrorCode::InvalidSignatureInstruction);
     
-    #[account(mut)]
-    pub player2_token_account: Account<'info, TokenAccount>,
+    let read_u16 = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+    let signature_ix_index = read_u16(4);
+    let public_key_offset = read_u16(6) as usize;
+    let public_key_ix_index = read_u16(8);
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    let message_ix_index = read_u16(14);
     
-    #[account(mut)]
-    pub treasury_token_account: Account<'info, TokenAccount>,
+    // Todos los datos deben estar dentro de la propia instrucción Ed25519
+    require!(
+        signature_ix_index == u16::MAX
+            && public_key_ix_index == u16::MAX
+            && message_ix_index == u16::MAX,
+        ErrorCode::InvalidSignatureInstruction
+    );
+    require!(
+        data.len() >= public_key_offset + 32 && data.len() >= message_offset + message_size,
+        ErrorCode::InvalidSignatureInstruction
+    );
     
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    require!(
+        &data[public_key_offset..public_key_offset + 32] == signer.as_ref(),
+        ErrorCode::SignatureMismatch
+    );
+    require!(
+        &data[message_offset..message_offset + message_size] == message,
+        ErrorCode::SignatureMismatch
+    );
     
-    pub token_program: Program<'info, Token>,
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct CancelGame<'info> {
-    #[account(
-        mut,
-        seeds = [b"game", &game_match.game_id.to_le_bytes()],
-        bump
-    )]
-    pub game_match: Account<'info, GameMatch>,
-    
-    #[account(mut, seeds = [b"game_counter"], bump)]
-    pub game_counter: Account<'info, GameCounter>,
-    
-    #[account(
-        mut,
-        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
-        bump
-    )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub player1_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub player1: Signer<'info>,
-    
-    pub token_program: Program<'info, Token>,
+/// Límites del control de tiempo (3 horas base, 3 minutos de incremento o delay)
+const MAX_BASE_SECONDS: u32 = 10800;
+const MAX_INCREMENT_SECONDS: u16 = 180;
+
+/// Validar un control de tiempo: base positiva y como mucho incremento o delay, no ambos
+fn is_valid_time_control(time_control: &TimeControl) -> bool {
+    time_control.base_seconds > 0
+        && time_control.base_seconds <= MAX_BASE_SECONDS
+        && time_control.increment_seconds <= MAX_INCREMENT_SECONDS
+        && time_control.delay_seconds <= MAX_INCREMENT_SECONDS
+        && (time_control.increment_seconds == 0 || time_control.delay_seconds == 0)
 }
 
-#[derive(Accounts)]
-pub struct TimeoutClaim<'info> {
-    #[account(
-        mut,
-        seeds = [b"game", &game_match.game_id.to_le_bytes()],
-        bump
-    )]
-    pub game_match: Account<'info, GameMatch>,
-    
-    #[account(mut, seeds = [b"game_counter"], bump)]
-    pub game_counter: Account<'info, GameCounter>,
-    
-    #[account(
-        mut,
-        seeds = [b"game_escrow", &game_match.game_id.to_le_bytes()],
-        bump
-    )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub claimer_token_account: Account<'info, TokenAccount>,
+/// Tiempo descontado al jugador que mueve (el delay no consume reloj)
+fn clock_time_used(game: &GameMatch, current_time: i64) -> i64 {
+    let elapsed = current_time.saturating_sub(game.clock_running_since);
+    elapsed.saturating_sub(game.time_control.delay_seconds as i64).max(0)
+}
+
+/// Indica si al jugador que mueve se le acabó el tiempo
+fn has_flag_fallen(game: &GameMatch, current_time: i64) -> bool {
+    let time_left = if game.player1_to_move { game.player1_time_left } else { game.player2_time_left };
+    clock_time_used(game, current_time) >= time_left
+}
+
+/// Descontar el tiempo del jugador que mueve, sumar incremento y pasar el turno
+fn press_game_clock(game: &mut GameMatch, current_time: i64) -> Result<()> {
+    require!(!has_flag_fallen(game, current_time), ErrorCode::FlagFallen);
     
-    #[account(mut)]
-    pub treasury_token_account: Account<'info, TokenAccount>,
+    let used = clock_time_used(game, current_time);
+    let increment = game.time_control.increment_seconds as i64;
+    let time_left = if game.player1_to_move {
+        &mut game.player1_time_left
+    } else {
+        &mut game.player2_time_left
+    };
+    *time_left = time_left.checked_sub(used).unwrap().checked_add(increment).unwrap();
     
-    #[account(mut)]
-    pub claimer: Signer<'info>,
+    // Jugar sin aceptar la oferta de tablas del oponente equivale a rechazarla
+    let mover = if game.player1_to_move { game.player1 } else { game.player2 };
+    if game.draw_offered_by.is_some() && game.draw_offered_by != Some(mover) {
+        game.draw_offered_by = None;
+    }
     
-    pub token_program: Program<'info, Token>,
+    game.player1_to_move = !game.player1_to_move;
+    game.clock_running_since = current_time;
+    game.moves_played = game.moves_played.saturating_add(1);
+    Ok(())
 }
 
-// ============= STATE =============
+/// Umbral de abandono por defecto: mínimo 5 minutos o 50% de la duración estimada
+const DEFAULT_ABANDONMENT_MIN_SECONDS: i64 = 300;
+const DEFAULT_ABANDONMENT_PERCENT: u16 = 50;
 
-#[account]
-#[derive(InitSpace)]
-pub struct TokenState {
-    pub is_initialized: bool,
-    pub authority: Pubkey,
-    pub pending_authority: Option<Pubkey>,
-    pub max_supply: u64,
-    pub current_supply: u64,
-    pub daily_mint_limit: u64,
-    pub minted_today: u64,
-    pub last_mint_reset: i64,
-    pub swap_fee_basis_points: u16,
-    pub chess_to_game_rate: u64,
-    pub is_paused: bool,
-    pub created_at: i64,
+/// Una partida está terminada (y puede cerrarse) cuando ya no queda nada por liquidar
+fn is_game_finished(status: GameStatus) -> bool {
+    matches!(
+        status,
+        GameStatus::Completed
+            | GameStatus::Draw
+            | GameStatus::Cancelled
+            | GameStatus::Timeout
+            | GameStatus::Aborted
+    )
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct Blacklist {
-    #[max_len(1000)]
-    pub addresses: Vec<Pubkey>,
+/// Número de shards del contador de partidas
+/// Cada partida usa el shard `game_id % GAME_COUNTER_SHARDS`, así las partidas
+/// independientes no compiten por la misma cuenta
+pub const GAME_COUNTER_SHARDS: u64 = 16;
+
+/// Plazo para que alguien se una a una partida (por defecto 24 horas, máximo 7 días)
+const DEFAULT_JOIN_WINDOW_SECONDS: i64 = 86400;
+const MIN_JOIN_WINDOW_SECONDS: i64 = 60;
+const MAX_JOIN_WINDOW_SECONDS: i64 = 604800;
+
+/// Ventana por defecto para abortar una partida sin fee (segundos desde join_game)
+const DEFAULT_ABORT_WINDOW_SECONDS: i64 = 60;
+
+/// Plazo por defecto para que un árbitro resuelva una disputa (7 días)
+const DEFAULT_DISPUTE_TIMEOUT_SECONDS: i64 = 604800;
+
+/// Jugadas estimadas por partida para calcular la duración con incremento o delay
+const ESTIMATED_MOVES_PER_GAME: i64 = 40;
+
+/// Segundos de inactividad tras los que se considera abandonada la partida
+/// Porcentaje de la duración estimada (base + 40 jugadas de incremento/delay), con un mínimo
+fn abandonment_threshold(config: &GameConfig, time_control: &TimeControl) -> i64 {
+    let per_move = time_control.increment_seconds as i64 + time_control.delay_seconds as i64;
+    let estimated_duration = time_control.base_seconds as i64 + ESTIMATED_MOVES_PER_GAME * per_move;
+    let threshold = estimated_duration * config.abandonment_percent as i64 / 100;
+    threshold.max(config.abandonment_min_seconds)
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct SwapRecord {
-    pub user: Pubkey,
-    #[max_len(10)]
-    pub from_token: String,
-    #[max_len(10)]
-    pub to_token: String,
-    pub from_amount: u64,
-    pub to_amount: u64,
-    pub fee: u64,
-    pub timestamp: i64,
-    pub processed: bool,
+/// Registrar la última actividad de un jugador
+fn record_activity(game: &mut GameMatch, player: Pubkey, current_time: i64) {
+    if player == game.player1 {
+        game.player1_last_active = current_time;
+    } else if player == game.player2 {
+        game.player2_last_active = current_time;
+    }
 }
 
-// Game Match State
-#[account]
-#[derive(InitSpace)]
-pub struct GameMatch {
-    pub game_id: u64,
-    pub player1: Pubkey,
-    pub player2: Pubkey,
-    pub bet_amount: u64,
-    pub total_pot: u64,
-    #[max_len(10)]
-    pub time_control: String,
-    #[max_len(50)]
-    pub title: String,
-    pub status: GameStatus,
-    pub winner: Option<Pubkey>,
-    pub created_at: i64,
-    pub started_at: i64,
-    pub ended_at: i64,
-    pub escrow_bump: u8,
+/// Leer la posición guardada en el tablero de una partida
+fn load_position(board: &ChessBoard) -> Position {
+    Position {
+        board: board.board,
+        white_to_move: board.white_to_move,
+        castling: board.castling,
+        en_passant: board.en_passant,
+        halfmove_clock: board.halfmove_clock,
+        fullmove_number: board.fullmove_number,
+    }
 }
 
-// Game Counter (global)
-#[account]
-#[derive(InitSpace)]
-pub struct GameCounter {
-    pub total_games: u64,
-    pub active_games: u64,
+/// Guardar una posición en el tablero de una partida
+fn store_position(board: &mut ChessBoard, position: &Position) {
+    board.board = position.board;
+    board.white_to_move = position.white_to_move;
+    board.castling = position.castling;
+    board.en_passant = position.en_passant;
+    board.halfmove_clock = position.halfmove_clock;
+    board.fullmove_number = position.fullmove_number;
 }
 
-// Game Status Enum
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
-pub enum GameStatus {
-    Waiting,    // Esperando oponente
-    Active,     // Partida en curso
-    Completed,  // Terminada con ganador
-    Draw,       // Empate
-    Cancelled,  // Cancelada antes de empezar
-    Timeout,    // Terminada por timeout
+/// Hash de 64 bits de la posición para detectar repeticiones
+fn position_hash(position: &Position) -> u64 {
+    let digest = hash(&position.repetition_key());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest.to_bytes()[..8]);
+    u64::from_le_bytes(bytes)
 }
 
-// ============= EVENTS =============
+/// Cuentas necesarias para pagar una partida desde su escrow
+pub struct EscrowPayout<'a, 'info> {
+    pub escrow_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    pub mint: &'a InterfaceAccount<'info, Mint>,
+    pub player1_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    pub player2_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    pub fees: &'a FeeAccountsCtx<'info>,
+    pub token_program: &'a Interface<'info, TokenInterface>,
+}
 
-#[event]
-pub struct ProgramInitialized {
-    pub authority: Pubkey,
-    pub max_supply: u64,
-    pub daily_mint_limit: u64,
-    pub swap_fee_basis_points: u16,
-    pub chess_to_game_rate: u64,
-    pub timestamp: i64,
+/// Fee del DAO por defecto para partidas (2.5% = 250 basis points)
+const DEFAULT_GAME_FEE_BASIS_POINTS: u16 = 250;
+/// Fee máximo configurable para partidas (10%)
+const MAX_GAME_FEE_BASIS_POINTS: u16 = 1000;
+pub const MAX_FEE_TIERS: usize = 5;
+/// Versión del layout de TokenState (0 = cuentas previas a los fees de partida)
+const TOKEN_STATE_VERSION: u8 = 1;
+
+/// Fee aplicable a una partida: las tablas usan su propio fee, y las victorias
+/// usan el tramo más alto cuyo `min_stake` cubra la apuesta (o el fee base)
+fn game_fee_basis_points(state: &TokenState, bet_amount: u64, is_draw: bool) -> u16 {
+    if is_draw {
+        return state.draw_fee_basis_points;
+    }
+    state.fee_tiers
+        .iter()
+        .rev()
+        .find(|tier| bet_amount >= tier.min_stake)
+        .map(|tier| tier.fee_basis_points)
+        .unwrap_or(state.game_fee_basis_points)
 }
 
-#[event]
-pub struct TokensMinted {
-    pub recipient: Pubkey,
-    pub amount: u64,
-    pub new_total_supply: u64,
-    pub minted_today: u64,
-    pub timestamp: i64,
+/// Cuenta dueña de un escrow PDA (partida o serie): aporta las seeds para firmar
+pub trait EscrowOwner {
+    fn escrow_seed(&self) -> &'static [u8];
+    fn escrow_id(&self) -> u64;
+    fn escrow_signer_bump(&self) -> u8;
+    fn fee_referrer(&self) -> Option<Pubkey>;
 }
 
-#[event]
-pub struct TokensBurned {
-    pub burner: Pubkey,
-    pub amount: u64,
-    pub new_total_supply: u64,
-    pub timestamp: i64,
+impl EscrowOwner for GameMatch {
+    fn escrow_seed(&self) -> &'static [u8] {
+        b"game_escrow"
+    }
+    fn escrow_id(&self) -> u64 {
+        self.game_id
+    }
+    fn escrow_signer_bump(&self) -> u8 {
+        self.escrow_bump
+    }
+    fn fee_referrer(&self) -> Option<Pubkey> {
+        self.referrer
+    }
 }
 
-#[event]
-pub struct SwapExecuted {
-    pub user: Pubkey,
-    pub from_token: String,
-    pub from_amount: u64,
-    pub to_token: String,
-    pub to_amount: u64,
-    pub fee: u64,
-    pub timestamp: i64,
+impl<'info, T> EscrowOwner for Account<'info, T>
+where
+    T: EscrowOwner + AccountSerialize + AccountDeserialize + Clone,
+{
+    fn escrow_seed(&self) -> &'static [u8] {
+        (**self).escrow_seed()
+    }
+    fn escrow_id(&self) -> u64 {
+        (**self).escrow_id()
+    }
+    fn escrow_signer_bump(&self) -> u8 {
+        (**self).escrow_signer_bump()
+    }
+    fn fee_referrer(&self) -> Option<Pubkey> {
+        (**self).fee_referrer()
+    }
 }
 
-#[event]
-pub struct EmergencyPause {
-    pub admin: Pubkey,
-    pub reason: String,
-    pub timestamp: i64,
+impl EscrowOwner for MatchSeries {
+    fn escrow_seed(&self) -> &'static [u8] {
+        b"series_escrow"
+    }
+    fn escrow_id(&self) -> u64 {
+        self.series_id
+    }
+    fn escrow_signer_bump(&self) -> u8 {
+        self.escrow_bump
+    }
+    fn fee_referrer(&self) -> Option<Pubkey> {
+        None
+    }
+}
+
+/// Máximo de partidas en una serie
+const MAX_SERIES_GAMES: u8 = 15;
+/// Plazo para resolver una partida de serie antes de que se pueda anular la serie
+const SERIES_GAME_TIMEOUT_SECONDS: i64 = 259200; // 3 días
+
+impl EscrowOwner for Tournament {
+    fn escrow_seed(&self) -> &'static [u8] {
+        b"tournament_escrow"
+    }
+    fn escrow_id(&self) -> u64 {
+        self.tournament_id
+    }
+    fn escrow_signer_bump(&self) -> u8 {
+        self.escrow_bump
+    }
+    fn fee_referrer(&self) -> Option<Pubkey> {
+        None
+    }
+}
+
+/// Límites de torneo (deben coincidir con los max_len de Tournament y TournamentRound)
+pub const MAX_TOURNAMENT_PLAYERS: usize = 32;
+pub const MAX_PAIRINGS: usize = MAX_TOURNAMENT_PLAYERS / 2;
+pub const MAX_PRIZE_PLACES: usize = 8;
+const MAX_SWISS_ROUNDS: u8 = 10;
+/// Índice de rival que marca un bye
+pub const TOURNAMENT_BYE: u8 = u8::MAX;
+/// Plazo para emparejar, cerrar o finalizar cada ronda antes de que se pueda reembolsar el torneo
+const TOURNAMENT_ROUND_TIMEOUT_SECONDS: i64 = 259200; // 3 días
+
+/// Rondas de una eliminatoria: ceil(log2(jugadores))
+fn knockout_rounds(players: usize) -> u8 {
+    (usize::BITS - (players - 1).leading_zeros()) as u8
+}
+
+/// Índices de jugadores ordenados por puntuación (empates por orden de inscripción)
+/// En eliminatoria las victorias ordenan solas: campeón, finalista, semifinalistas...
+fn tournament_standings(tournament: &Tournament) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..tournament.players.len()).collect();
+    order.sort_by(|a, b| tournament.players[*b].score.cmp(&tournament.players[*a].score));
+    order
+}
+
+/// Premio de cada puesto según la tabla (basis points del prize pool)
+/// El redondeo, y lo que no sume la tabla, va al primer puesto
+fn prize_shares(prize_pool: u64, prize_table: &[u16]) -> Vec<u64> {
+    let mut prizes: Vec<u64> = prize_table.iter()
+        .map(|bps| prize_pool.checked_mul(*bps as u64).unwrap().checked_div(10000).unwrap())
+        .collect();
+    prizes[0] += prize_pool - prizes.iter().sum::<u64>();
+    prizes
+}
+
+impl EscrowOwner for Arena {
+    fn escrow_seed(&self) -> &'static [u8] {
+        b"arena_escrow"
+    }
+    fn escrow_id(&self) -> u64 {
+        self.arena_id
+    }
+    fn escrow_signer_bump(&self) -> u8 {
+        self.escrow_bump
+    }
+    fn fee_referrer(&self) -> Option<Pubkey> {
+        None
+    }
+}
+
+/// Límites de arena (MAX_ARENA_PLAYERS debe coincidir con el max_len de Arena)
+pub const MAX_ARENA_PLAYERS: usize = 64;
+const MAX_ARENA_DURATION_SECONDS: i64 = 86400;
+/// Plazo tras el final para resolver las partidas en curso; después cuentan como sin resultado
+const ARENA_RESULT_GRACE_SECONDS: i64 = 86400;
+/// Puntos de arena: victoria y tablas; en racha se multiplican
+const ARENA_WIN_POINTS: u32 = 2;
+const ARENA_DRAW_POINTS: u32 = 1;
+const ARENA_STREAK_MULTIPLIER: u32 = 2;
+/// Victorias seguidas necesarias para estar en racha
+const ARENA_STREAK_THRESHOLD: u8 = 2;
+
+enum ArenaOutcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+/// Sumar el resultado de una partida a un jugador de arena; devuelve los puntos ganados
+/// Estando en racha (ARENA_STREAK_THRESHOLD victorias seguidas) la partida vale doble;
+/// tablas o derrota cortan la racha
+fn apply_arena_result(entry: &mut ArenaPlayer, outcome: ArenaOutcome) -> u32 {
+    let multiplier = if entry.streak >= ARENA_STREAK_THRESHOLD { ARENA_STREAK_MULTIPLIER } else { 1 };
+    let points = match outcome {
+        ArenaOutcome::Win => {
+            entry.streak = entry.streak.saturating_add(1);
+            ARENA_WIN_POINTS * multiplier
+        }
+        ArenaOutcome::Draw => {
+            entry.streak = 0;
+            ARENA_DRAW_POINTS * multiplier
+        }
+        ArenaOutcome::Loss => {
+            entry.streak = 0;
+            0
+        }
+    };
+    entry.points = entry.points.checked_add(points).unwrap();
+    entry.games_played = entry.games_played.saturating_add(1);
+    entry.in_game = false;
+    points
+}
+
+/// Índices de jugadores de la arena ordenados por puntos (empates por orden de inscripción)
+/// Quien no jugó ninguna partida no entra en la clasificación
+fn arena_standings(arena: &Arena) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..arena.players.len())
+        .filter(|i| arena.players[*i].games_played > 0)
+        .collect();
+    order.sort_by(|a, b| arena.players[*b].points.cmp(&arena.players[*a].points));
+    order
+}
+
+// El vault del squad firma como un escrow más
+impl EscrowOwner for Squad {
+    fn escrow_seed(&self) -> &'static [u8] {
+        b"squad_vault"
+    }
+    fn escrow_id(&self) -> u64 {
+        self.squad_id
+    }
+    fn escrow_signer_bump(&self) -> u8 {
+        self.vault_bump
+    }
+    fn fee_referrer(&self) -> Option<Pubkey> {
+        None
+    }
+}
+
+impl EscrowOwner for TeamBattle {
+    fn escrow_seed(&self) -> &'static [u8] {
+        b"team_battle_escrow"
+    }
+    fn escrow_id(&self) -> u64 {
+        self.battle_id
+    }
+    fn escrow_signer_bump(&self) -> u8 {
+        self.escrow_bump
+    }
+    fn fee_referrer(&self) -> Option<Pubkey> {
+        None
+    }
 }
 
-#[event]
-pub struct ProgramUnpaused {
-    pub admin: Pubkey,
-    pub timestamp: i64,
+/// Límites de squads (MAX_SQUAD_MEMBERS debe coincidir con el max_len de Squad)
+pub const MAX_SQUAD_MEMBERS: usize = 20;
+const MAX_SQUAD_NAME_LENGTH: usize = 32;
+const MAX_TEAM_BATTLE_DURATION_SECONDS: i64 = 604800; // 7 días
+/// Plazo tras el final para resolver las partidas en curso; después cuentan como sin resultado
+const BATTLE_RESULT_GRACE_SECONDS: i64 = 86400;
+
+#[derive(PartialEq, Eq)]
+enum BattleSide {
+    SquadA,
+    SquadB,
 }
 
-#[event]
-pub struct AddressBlacklisted {
-    pub address: Pubkey,
-    pub by: Pubkey,
-    pub timestamp: i64,
+/// Squad por el que juega un jugador en una batalla
+/// Quien esté en los dos squads no puede jugar la batalla
+fn battle_side(squad_a: &Squad, squad_b: &Squad, player: &Pubkey) -> Result<BattleSide> {
+    match (squad_a.members.contains(player), squad_b.members.contains(player)) {
+        (true, false) => Ok(BattleSide::SquadA),
+        (false, true) => Ok(BattleSide::SquadB),
+        (true, true) => err!(ErrorCode::SameSquad),
+        (false, false) => err!(ErrorCode::NotSquadMember),
+    }
 }
 
-#[event]
-pub struct AddressRemovedFromBlacklist {
-    pub address: Pubkey,
-    pub by: Pubkey,
-    pub timestamp: i64,
+/// Resultado de una serie si ya está decidida: Some(Some(ganador)), Some(None) si
+/// acaba empatada, None si sigue en juego
+fn series_outcome(series: &MatchSeries) -> Option<Option<Pubkey>> {
+    let p1 = series.player1_points;
+    let p2 = series.player2_points;
+    let leader = |p1: u16, p2: u16| if p1 > p2 { Some(series.player1) } else { Some(series.player2) };
+    
+    if series.games_played >= series.games_planned {
+        return Some(if p1 == p2 { None } else { leader(p1, p2) });
+    }
+    if series.format == SeriesFormat::BestOf {
+        // Decidida si el rival ya no puede alcanzar al líder con las partidas restantes
+        let remaining = (series.games_planned - series.games_played) as u16 * 2;
+        if p1 > p2 + remaining || p2 > p1 + remaining {
+            return Some(leader(p1, p2));
+        }
+    }
+    None
 }
 
-#[event]
-pub struct AuthorityTransferInitiated {
-    pub old_authority: Pubkey,
-    pub new_authority: Pubkey,
-    pub timestamp: i64,
+/// Las partidas de serie, arena o batalla usan el escrow de su evento, no uno propio
+fn has_game_escrow(game: &GameMatch) -> bool {
+    game.series_id.is_none() && game.arena_id.is_none() && game.team_battle_id.is_none()
 }
 
-#[event]
-pub struct AuthorityTransferred {
-    pub old_authority: Pubkey,
-    pub new_authority: Pubkey,
-    pub timestamp: i64,
+/// Transferir desde el escrow de la partida firmando como el PDA
+fn transfer_from_escrow<'info>(
+    payout: &EscrowPayout<'_, 'info>,
+    game: &GameMatch,
+    to: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    transfer_from_escrow_account(
+        payout.token_program,
+        payout.escrow_token_account,
+        payout.mint,
+        game,
+        to,
+        amount,
+    )
 }
 
-#[event]
-pub struct ConfigUpdated {
-    pub daily_mint_limit: u64,
-    pub swap_fee_basis_points: u16,
-    pub by: Pubkey,
-    pub timestamp: i64,
+/// Transferir desde una cuenta escrow (de partida o de serie) firmando como el PDA
+/// Usa transfer_checked para que funcione con Token y Token-2022
+fn transfer_from_escrow_account<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    escrow_token_account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    owner: &impl EscrowOwner,
+    to: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let id_bytes = owner.escrow_id().to_le_bytes();
+    let bump = [owner.escrow_signer_bump()];
+    let seeds = &[owner.escrow_seed(), id_bytes.as_ref(), &bump];
+    let signer_seeds = &[&seeds[..]];
+    
+    let cpi_accounts = TransferChecked {
+        from: escrow_token_account.to_account_info(),
+        mint: mint.to_account_info(),
+        to,
+        authority: escrow_token_account.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        cpi_accounts,
+        signer_seeds,
+    );
+    token_interface::transfer_checked(cpi_ctx, amount, mint.decimals)
 }
 
-// Game Escrow Events
-#[event]
-pub struct GameCreated {
-    pub game_id: u64,
-    pub player1: Pubkey,
-    pub bet_amount: u64,
-    pub time_control: String,
-    pub title: String,
-    pub timestamp: i64,
+/// Cerrar una cuenta escrow (vacía) y enviar la renta a `destination`
+fn close_escrow_account<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    escrow_token_account: &InterfaceAccount<'info, TokenAccount>,
+    owner: &impl EscrowOwner,
+    destination: AccountInfo<'info>,
+) -> Result<()> {
+    let id_bytes = owner.escrow_id().to_le_bytes();
+    let bump = [owner.escrow_signer_bump()];
+    let seeds = &[owner.escrow_seed(), id_bytes.as_ref(), &bump];
+    let signer_seeds = &[&seeds[..]];
+    
+    let cpi_accounts = CloseAccount {
+        account: escrow_token_account.to_account_info(),
+        destination,
+        authority: escrow_token_account.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        cpi_accounts,
+        signer_seeds,
+    );
+    token_interface::close_account(cpi_ctx)
 }
 
-#[event]
-pub struct GameJoined {
-    pub game_id: u64,
-    pub player1: Pubkey,
-    pub player2: Pubkey,
-    pub total_pot: u64,
-    pub timestamp: i64,
+/// Cerrar el tablero de una partida trustless si llegó a crearse
+/// Devuelve la renta recuperada (0 si el PDA no existe)
+fn close_board<'info>(board: &AccountInfo<'info>, destination: AccountInfo<'info>) -> Result<u64> {
+    if board.owner != &crate::ID {
+        return Ok(0);
+    }
+    require!(
+        board.try_borrow_data()?.get(..8) == Some(&ChessBoard::DISCRIMINATOR[..]),
+        ErrorCode::BoardRequired
+    );
+    
+    let lamports = board.lamports();
+    **destination.try_borrow_mut_lamports()? += lamports;
+    **board.try_borrow_mut_lamports()? = 0;
+    board.assign(&anchor_lang::system_program::ID);
+    board.realloc(0, false)?;
+    Ok(lamports)
 }
 
-#[event]
-pub struct GameResolved {
-    pub game_id: u64,
-    pub winner: Option<Pubkey>,
-    pub is_draw: bool,
-    pub prize_amount: u64,
-    pub dao_fee: u64,
-    pub timestamp: i64,
+/// Quemar tokens de una cuenta escrow firmando como el PDA
+fn burn_from_escrow_account<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    escrow_token_account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    owner: &impl EscrowOwner,
+    amount: u64,
+) -> Result<()> {
+    let id_bytes = owner.escrow_id().to_le_bytes();
+    let bump = [owner.escrow_signer_bump()];
+    let seeds = &[owner.escrow_seed(), id_bytes.as_ref(), &bump];
+    let signer_seeds = &[&seeds[..]];
+    
+    let cpi_accounts = Burn {
+        mint: mint.to_account_info(),
+        from: escrow_token_account.to_account_info(),
+        authority: escrow_token_account.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        cpi_accounts,
+        signer_seeds,
+    );
+    token_interface::burn(cpi_ctx, amount)
 }
 
-#[event]
-pub struct GameCancelled {
-    pub game_id: u64,
-    pub player1: Pubkey,
-    pub refund_amount: u64,
-    pub timestamp: i64,
+/// Reparto de un empate proporcional a los depósitos de cada jugador
+fn draw_shares(game: &GameMatch, prize_pool: u64) -> (u64, u64) {
+    let total_deposits = game.player1_deposit as u128 + game.player2_deposit as u128;
+    if total_deposits == 0 {
+        return (0, 0);
+    }
+    let player1_share = (prize_pool as u128 * game.player1_deposit as u128 / total_deposits) as u64;
+    (player1_share, prize_pool - player1_share)
 }
 
-#[event]
-pub struct GameTimeout {
-    pub game_id: u64,
-    pub claimer: Pubkey,
-    pub prize_amount: u64,
-    pub dao_fee: u64,
-    pub timestamp: i64,
+/// Liquidar una partida activa
+/// Victoria: el ganador recibe el prize pool. Empate: el prize pool se reparte
+/// en proporción a lo que depositó cada jugador (50% / 50% si apostaron lo mismo).
+/// Devuelve (premio total pagado a jugadores, fee del DAO, ...)
+fn settle_game<'info>(
+    game: &mut GameMatch,
+    payout: &EscrowPayout<'_, 'info>,
+    state: &TokenState,
+    winner_key: Pubkey,
+    is_draw: bool,
+    current_time: i64,
+) -> Result<(u64, u64, u16, FeeDistribution)> {
+    // Calcular fee del DAO según la tabla configurada en TokenState
+    let fee_basis_points = game_fee_basis_points(state, game.largest_stake(), is_draw);
+    let dao_fee = game.total_pot
+        .checked_mul(fee_basis_points as u64).unwrap()
+        .checked_div(10000).unwrap();
+    let prize_pool = game.total_pot.checked_sub(dao_fee).unwrap();
+    
+    let prize_amount = if is_draw {
+        // Empate: prize pool proporcional a los depósitos
+        game.status = GameStatus::Draw;
+        game.winner = None;
+        
+        let (player1_share, player2_share) = draw_shares(game, prize_pool);
+        transfer_from_escrow(payout, game, payout.player1_token_account.to_account_info(), player1_share)?;
+        transfer_from_escrow(payout, game, payout.player2_token_account.to_account_info(), player2_share)?;
+        player1_share + player2_share
+    } else {
+        // Victoria: ganador recibe prize pool completo
+        require!(
+            winner_key == game.player1 || winner_key == game.player2,
+            ErrorCode::InvalidWinner
+        );
+        
+        game.status = GameStatus::Completed;
+        game.winner = Some(winner_key);
+        
+        let winner_token_account = if winner_key == game.player1 {
+            payout.player1_token_account.to_account_info()
+        } else {
+            payout.player2_token_account.to_account_info()
+        };
+        transfer_from_escrow(payout, game, winner_token_account, prize_pool)?;
+        prize_pool
+    };
+    
+    // Repartir el fee entre sus destinos
+    let fee_distribution = distribute_escrow_fee(
+        payout.token_program,
+        payout.escrow_token_account,
+        payout.mint,
+        payout.fees,
+        game,
+        &state.fee_split,
+        dao_fee,
+    )?;
+    
+    game.ended_at = current_time;
+    Ok((prize_amount, dao_fee, fee_basis_points, fee_distribution))
 }
 
-// ============= HELPERS =============
+/// Dividir un fee según el reparto configurado; el redondeo y las partes sin
+/// destino (sin pool de staking o sin referrer) van al treasury
+fn split_fee(split: &FeeSplit, fee: u64, has_staking: bool, has_referrer: bool) -> FeeDistribution {
+    let share = |bps: u16| fee.checked_mul(bps as u64).unwrap().checked_div(10000).unwrap();
+    let burned = share(split.burn_bps);
+    let staking = if has_staking { share(split.staking_bps) } else { 0 };
+    let referrer = if has_referrer { share(split.referrer_bps) } else { 0 };
+    FeeDistribution {
+        treasury: fee - burned - staking - referrer,
+        burned,
+        staking,
+        referrer,
+    }
+}
 
-fn is_blacklisted(blacklist: &Account<Blacklist>, address: &Pubkey) -> bool {
-    blacklist.addresses.contains(address)
+/// Repartir un fee que está en un escrow (de partida o de serie)
+fn distribute_escrow_fee<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    escrow_token_account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    fees: &FeeAccountsCtx<'info>,
+    owner: &impl EscrowOwner,
+    split: &FeeSplit,
+    fee: u64,
+) -> Result<FeeDistribution> {
+    if let Some(referrer) = fees.referrer_token_account.as_ref() {
+        require!(
+            Some(referrer.owner) == owner.fee_referrer(),
+            ErrorCode::InvalidTokenAccount
+        );
+    }
+    // Sin la cuenta del referrer su parte va al treasury: las liquidaciones
+    // permissionless no pueden depender de encontrar esa cuenta
+    let distribution = split_fee(
+        split,
+        fee,
+        fees.wager_mint.staking_pool != Pubkey::default(),
+        owner.fee_referrer().is_some() && fees.referrer_token_account.is_some(),
+    );
+    
+    if distribution.burned > 0 {
+        burn_from_escrow_account(token_program, escrow_token_account, mint, owner, distribution.burned)?;
+    }
+    if distribution.staking > 0 {
+        let staking = fees.staking_token_account.as_ref().ok_or(ErrorCode::MissingFeeAccount)?;
+        transfer_from_escrow_account(
+            token_program,
+            escrow_token_account,
+            mint,
+            owner,
+            staking.to_account_info(),
+            distribution.staking,
+        )?;
+    }
+    if distribution.referrer > 0 {
+        let referrer = fees.referrer_token_account.as_ref().ok_or(ErrorCode::MissingFeeAccount)?;
+        transfer_from_escrow_account(
+            token_program,
+            escrow_token_account,
+            mint,
+            owner,
+            referrer.to_account_info(),
+            distribution.referrer,
+        )?;
+    }
+    if distribution.treasury > 0 {
+        transfer_from_escrow_account(
+            token_program,
+            escrow_token_account,
+            mint,
+            owner,
+            fees.treasury_token_account.to_account_info(),
+            distribution.treasury,
+        )?;
+    }
+    
+    Ok(distribution)
 }
 
 // ============= ERRORS =============
@@ -1363,6 +9356,189 @@ pub enum ErrorCode {
     #[msg("Cannot join your own game")]
     CannotJoinOwnGame,
     
+    #[msg("This game is reserved for an invited opponent")]
+    NotInvited,
+    
+    #[msg("Invalid or missing invite secret")]
+    InvalidInviteSecret,
+    
+    #[msg("Join window must be between 1 minute and 7 days")]
+    InvalidJoinWindow,
+    
+    #[msg("Game join deadline has passed")]
+    GameExpired,
+    
+    #[msg("Game join deadline has not passed yet")]
+    GameNotExpired,
+    
+    #[msg("Game is not finished")]
+    GameNotFinished,
+    
+    #[msg("Too many fee tiers")]
+    TooManyFeeTiers,
+    
+    #[msg("Fee tiers must have strictly increasing minimum stakes")]
+    InvalidFeeTiers,
+    
+    #[msg("Fee split weights must sum to 10000 basis points")]
+    InvalidFeeSplit,
+    
+    #[msg("Staking pool is not configured or does not match")]
+    InvalidStakingPool,
+    
+    #[msg("A fee destination account required by the fee split is missing")]
+    MissingFeeAccount,
+    
+    #[msg("Referrer cannot be the paying player")]
+    SelfReferral,
+    
+    #[msg("Mint is not allowed for wagers")]
+    MintNotAllowed,
+    
+    #[msg("Bet amount is outside the stake limits for this mint")]
+    StakeOutOfRange,
+    
+    #[msg("Invalid stake limits")]
+    InvalidStakeLimits,
+    
+    #[msg("Invalid series length (best-of series need an odd number of games)")]
+    InvalidSeriesLength,
+    
+    #[msg("A game of this series is already in progress")]
+    SeriesGameInProgress,
+    
+    #[msg("Game is not the current game of this series")]
+    NotSeriesGame,
+    
+    #[msg("Game ID is already in use")]
+    GameIdTaken,
+    
+    #[msg("A rematch with a different game ID already exists")]
+    RematchAlreadyCreated,
+    
+    #[msg("Player has already deposited")]
+    AlreadyDeposited,
+    
+    #[msg("Rematch games are funded through the rematch instruction")]
+    RematchGame,
+    
+    #[msg("Series, arena and team battle games cannot be rematched")]
+    RematchNotAllowed,
+    
+    #[msg("Opponent already deposited; the game can only expire")]
+    OpponentAlreadyDeposited,
+    
+    #[msg("Invalid tournament size or number of rounds")]
+    InvalidTournamentSize,
+    
+    #[msg("Prize table must have between 1 and 8 places summing to 10000 basis points")]
+    InvalidPrizeTable,
+    
+    #[msg("Invalid deadline")]
+    InvalidDeadline,
+    
+    #[msg("Tournament registration is closed")]
+    RegistrationClosed,
+    
+    #[msg("Tournament is full")]
+    TournamentFull,
+    
+    #[msg("Player is already registered")]
+    AlreadyRegistered,
+    
+    #[msg("The organizer cannot play in their own tournament")]
+    OrganizerCannotPlay,
+    
+    #[msg("Not enough players to start the tournament")]
+    NotEnoughPlayers,
+    
+    #[msg("Tournament is not running")]
+    TournamentNotRunning,
+    
+    #[msg("Current round still has pending results")]
+    RoundInProgress,
+    
+    #[msg("Invalid round")]
+    InvalidRound,
+    
+    #[msg("Invalid pairings")]
+    InvalidPairings,
+    
+    #[msg("Invalid pairing result")]
+    InvalidPairingResult,
+    
+    #[msg("Result already reported for this pairing")]
+    ResultAlreadyReported,
+    
+    #[msg("Tournament has rounds left to play")]
+    TournamentNotFinished,
+    
+    #[msg("Tournament round deadline has not passed")]
+    TournamentNotStalled,
+    
+    #[msg("Tournament has played all its rounds and must be finalized")]
+    TournamentFinalizable,
+    
+    #[msg("Arena is not open")]
+    ArenaNotOpen,
+    
+    #[msg("Arena is full")]
+    ArenaFull,
+    
+    #[msg("Arena has not started yet")]
+    ArenaNotStarted,
+    
+    #[msg("Arena has already started")]
+    ArenaAlreadyStarted,
+    
+    #[msg("Arena clock has ended")]
+    ArenaEnded,
+    
+    #[msg("Arena clock has not ended yet")]
+    ArenaNotEnded,
+    
+    #[msg("Arena still has games in progress")]
+    ArenaGamesInProgress,
+    
+    #[msg("Player is not registered")]
+    NotRegistered,
+    
+    #[msg("Player is already in a game")]
+    PlayerInGame,
+    
+    #[msg("Game does not belong to this arena")]
+    NotArenaGame,
+    
+    #[msg("Squad name too long")]
+    SquadNameTooLong,
+    
+    #[msg("Squad is full")]
+    SquadFull,
+    
+    #[msg("Already a squad member")]
+    AlreadySquadMember,
+    
+    #[msg("Not a squad member")]
+    NotSquadMember,
+    
+    #[msg("Squad members cannot change during a team battle")]
+    SquadInBattle,
+    
+    #[msg("Both sides belong to the same squad")]
+    SameSquad,
+    
+    #[msg("Team battle has ended")]
+    BattleEnded,
+    
+    #[msg("Team battle has not ended yet")]
+    BattleNotEnded,
+    
+    #[msg("Team battle still has games in progress")]
+    BattleGamesInProgress,
+    
+    #[msg("Game does not belong to this team battle")]
+    NotBattleGame,
+    
     #[msg("Invalid winner address")]
     InvalidWinner,
     
@@ -1371,4 +9547,333 @@ pub enum ErrorCode {
     
     #[msg("Timeout period has not been reached yet")]
     TimeoutNotReached,
+    
+    #[msg("Token account does not belong to the expected owner")]
+    InvalidTokenAccount,
+    
+    #[msg("Treasury account does not match the configured treasury")]
+    InvalidTreasury,
+    
+    // Oracle Errors
+    #[msg("Oracle is not registered")]
+    OracleNotRegistered,
+    
+    #[msg("Oracle is already registered")]
+    OracleAlreadyRegistered,
+    
+    #[msg("Oracle registry is full")]
+    OracleRegistryFull,
+    
+    #[msg("Signed result has expired")]
+    ResultExpired,
+    
+    #[msg("Missing Ed25519 signature instruction")]
+    MissingSignatureInstruction,
+    
+    #[msg("Malformed Ed25519 signature instruction")]
+    InvalidSignatureInstruction,
+    
+    #[msg("Signature does not match the expected signer or message")]
+    SignatureMismatch,
+    
+    #[msg("Game stake requires an oracle quorum")]
+    QuorumRequired,
+    
+    #[msg("Invalid quorum size")]
+    InvalidQuorum,
+    
+    #[msg("Oracle has already voted on this game")]
+    OracleAlreadyVoted,
+    
+    #[msg("The quorum vote must pass the account that paid for the pending result")]
+    InvalidPendingPayer,
+    
+    // Optimistic Settlement Errors
+    #[msg("Challenge window must be positive")]
+    InvalidChallengeWindow,
+    
+    #[msg("Too many arbiters (max 5)")]
+    TooManyArbiters,
+    
+    #[msg("Game is not pending settlement")]
+    GameNotPendingSettlement,
+    
+    #[msg("Challenge window is still open")]
+    ChallengeWindowOpen,
+    
+    #[msg("Challenge window has closed")]
+    ChallengeWindowClosed,
+    
+    #[msg("Game is not disputed")]
+    GameNotDisputed,
+    
+    #[msg("Signer is not a configured arbiter")]
+    NotAnArbiter,
+    
+    #[msg("Optimistic settlement is enabled; propose the result instead")]
+    OptimisticSettlementRequired,
+    
+    #[msg("Dispute timeout must be positive")]
+    InvalidDisputeTimeout,
+    
+    #[msg("Dispute ruling deadline has not passed")]
+    DisputeNotExpired,
+    
+    // Trustless Game Errors
+    #[msg("Trustless games can only be settled by their moves")]
+    TrustlessGame,
+    
+    #[msg("Game is not in trustless mode")]
+    NotTrustlessGame,
+    
+    #[msg("Trustless games must pass their chess board account")]
+    BoardRequired,
+    
+    #[msg("It is not your turn")]
+    NotYourTurn,
+    
+    #[msg("Illegal move")]
+    IllegalMove,
+    
+    // Chess Clock Errors
+    #[msg("Your time has run out")]
+    FlagFallen,
+    
+    #[msg("Opponent still has time on the clock")]
+    FlagNotFallen,
+    
+    // Game Record Errors
+    #[msg("Game has no committed move record")]
+    NoGameRecord,
+    
+    #[msg("Invalid move inclusion proof")]
+    InvalidMoveProof,
+    
+    #[msg("Invalid position encoding")]
+    InvalidPosition,
+    
+    #[msg("Move is legal, fraud proof rejected")]
+    MoveIsLegal,
+    
+    #[msg("Record leaves are consistent, fraud proof rejected")]
+    RecordIsConsistent,
+    
+    // Player Ending Errors
+    #[msg("Opponent has not offered a draw")]
+    NoDrawOffer,
+    
+    #[msg("Game can no longer be aborted")]
+    AbortWindowClosed,
+    
+    #[msg("Abort window cannot be negative")]
+    InvalidAbortWindow,
+    
+    // Abandonment Errors
+    #[msg("Invalid abandonment threshold")]
+    InvalidAbandonmentThreshold,
+    
+    #[msg("Claimer has been inactive longer than the opponent")]
+    ClaimerInactive,
+    
+    // Migration Errors
+    #[msg("Token state is already on the current version")]
+    TokenStateUpToDate,
+    
+    #[msg("Token state account has an unknown layout")]
+    InvalidTokenStateLayout,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cuenta con todos los campos a cero (solo para tests)
+    fn zeroed<T: AnchorDeserialize + Space>() -> T {
+        T::deserialize(&mut &vec![0u8; T::INIT_SPACE][..]).unwrap()
+    }
+
+    fn series(format: SeriesFormat, games_planned: u8, games_played: u8, points: (u16, u16)) -> MatchSeries {
+        let mut series: MatchSeries = zeroed();
+        series.player1 = Pubkey::new_unique();
+        series.player2 = Pubkey::new_unique();
+        series.format = format;
+        series.games_planned = games_planned;
+        series.games_played = games_played;
+        series.player1_points = points.0;
+        series.player2_points = points.1;
+        series
+    }
+
+    fn arena_player(points: u32, games_played: u16) -> ArenaPlayer {
+        ArenaPlayer {
+            player: Pubkey::new_unique(),
+            deposit: 100,
+            points,
+            streak: 0,
+            games_played,
+            in_game: false,
+        }
+    }
+
+    #[test]
+    fn fee_tiers() {
+        let mut state: TokenState = zeroed();
+        state.game_fee_basis_points = 250;
+        state.draw_fee_basis_points = 100;
+        state.fee_tiers = vec![
+            FeeTier { min_stake: 1_000, fee_basis_points: 200 },
+            FeeTier { min_stake: 10_000, fee_basis_points: 150 },
+        ];
+
+        assert_eq!(game_fee_basis_points(&state, 999, false), 250);
+        assert_eq!(game_fee_basis_points(&state, 1_000, false), 200);
+        assert_eq!(game_fee_basis_points(&state, 9_999, false), 200);
+        assert_eq!(game_fee_basis_points(&state, 10_000, false), 150);
+        // Las tablas ignoran los tramos
+        assert_eq!(game_fee_basis_points(&state, 10_000, true), 100);
+
+        state.fee_tiers.clear();
+        assert_eq!(game_fee_basis_points(&state, u64::MAX, false), 250);
+    }
+
+    #[test]
+    fn split_fee_rounding_goes_to_treasury() {
+        let split = FeeSplit { treasury_bps: 4000, burn_bps: 3000, staking_bps: 2000, referrer_bps: 1000 };
+
+        let all = split_fee(&split, 999, true, true);
+        assert_eq!(all, FeeDistribution { treasury: 402, burned: 299, staking: 199, referrer: 99 });
+
+        // Sin pool de staking ni referrer sus partes van al treasury
+        let none = split_fee(&split, 999, false, false);
+        assert_eq!(none, FeeDistribution { treasury: 700, burned: 299, staking: 0, referrer: 0 });
+
+        assert_eq!(split_fee(&split, 0, true, true), FeeDistribution::default());
+    }
+
+    #[test]
+    fn draw_shares_follow_deposits() {
+        let mut game: GameMatch = zeroed();
+        game.player1_deposit = 100;
+        game.player2_deposit = 100;
+        // El redondeo favorece a player2: entre los dos siempre suman el prize pool
+        assert_eq!(draw_shares(&game, 195), (97, 98));
+
+        game.player1_deposit = 300;
+        assert_eq!(draw_shares(&game, 390), (292, 98));
+
+        game.player1_deposit = 0;
+        game.player2_deposit = 0;
+        assert_eq!(draw_shares(&game, 390), (0, 0));
+    }
+
+    #[test]
+    fn best_of_series_ends_when_decided() {
+        let open = series(SeriesFormat::BestOf, 3, 1, (2, 0));
+        assert_eq!(series_outcome(&open), None);
+
+        let decided = series(SeriesFormat::BestOf, 3, 2, (4, 0));
+        assert_eq!(series_outcome(&decided), Some(Some(decided.player1)));
+
+        let level = series(SeriesFormat::BestOf, 3, 2, (2, 2));
+        assert_eq!(series_outcome(&level), None);
+
+        let player2_wins = series(SeriesFormat::BestOf, 3, 3, (2, 4));
+        assert_eq!(series_outcome(&player2_wins), Some(Some(player2_wins.player2)));
+
+        let drawn = series(SeriesFormat::BestOf, 3, 3, (3, 3));
+        assert_eq!(series_outcome(&drawn), Some(None));
+    }
+
+    #[test]
+    fn fixed_series_plays_every_game() {
+        // Aunque el marcador ya no pueda darse la vuelta se juegan todas las partidas
+        let open = series(SeriesFormat::FixedGames, 4, 3, (6, 0));
+        assert_eq!(series_outcome(&open), None);
+
+        let finished = series(SeriesFormat::FixedGames, 4, 4, (6, 2));
+        assert_eq!(series_outcome(&finished), Some(Some(finished.player1)));
+
+        let drawn = series(SeriesFormat::FixedGames, 4, 4, (4, 4));
+        assert_eq!(series_outcome(&drawn), Some(None));
+    }
+
+    #[test]
+    fn prize_shares_leftover_goes_to_first() {
+        assert_eq!(prize_shares(1_001, &[5000, 3000, 2000]), vec![501, 300, 200]);
+        // Lo que no reparte la tabla también va al primero
+        assert_eq!(prize_shares(1_000, &[6000, 2000]), vec![800, 200]);
+        assert_eq!(prize_shares(7, &[10000]), vec![7]);
+        assert_eq!(prize_shares(0, &[5000, 5000]), vec![0, 0]);
+    }
+
+    #[test]
+    fn arena_streak_doubles_points() {
+        let mut entry = arena_player(0, 0);
+        entry.in_game = true;
+
+        assert_eq!(apply_arena_result(&mut entry, ArenaOutcome::Win), 2);
+        assert_eq!(apply_arena_result(&mut entry, ArenaOutcome::Win), 2);
+        assert_eq!(entry.streak, 2);
+        // En racha: victoria y tablas valen doble
+        assert_eq!(apply_arena_result(&mut entry, ArenaOutcome::Win), 4);
+        assert_eq!(apply_arena_result(&mut entry, ArenaOutcome::Draw), 2);
+        assert_eq!(entry.streak, 0);
+        assert_eq!(apply_arena_result(&mut entry, ArenaOutcome::Draw), 1);
+        assert_eq!(apply_arena_result(&mut entry, ArenaOutcome::Loss), 0);
+
+        assert_eq!(entry.points, 11);
+        assert_eq!(entry.games_played, 6);
+        assert!(!entry.in_game);
+    }
+
+    #[test]
+    fn arena_standings_skip_players_without_games() {
+        let mut arena: Arena = zeroed();
+        arena.players = vec![
+            arena_player(3, 2),
+            arena_player(0, 0),
+            arena_player(5, 3),
+            arena_player(3, 1),
+            arena_player(0, 1),
+        ];
+        // Empates por orden de inscripción; quien perdió todo sí clasifica
+        assert_eq!(arena_standings(&arena), vec![2, 0, 3, 4]);
+
+        arena.players = vec![arena_player(0, 0), arena_player(0, 0)];
+        assert!(arena_standings(&arena).is_empty());
+    }
+
+    #[test]
+    fn merkle_proof_with_odd_level() {
+        let node = |left: &[u8; 32], right: &[u8; 32]| hashv(&[&[1u8], left, right]).to_bytes();
+        let leaves = [hash(b"e4").to_bytes(), hash(b"e5").to_bytes(), hash(b"Nf3").to_bytes()];
+        let left = node(&leaves[0], &leaves[1]);
+        let right = node(&leaves[2], &leaves[2]);
+        let root = node(&left, &right);
+
+        assert!(verify_merkle_proof(&root, leaves[0], 0, &[leaves[1], right]));
+        assert!(verify_merkle_proof(&root, leaves[1], 1, &[leaves[0], right]));
+        assert!(verify_merkle_proof(&root, leaves[2], 2, &[leaves[2], left]));
+
+        // Hoja, posición o profundidad incorrectas
+        assert!(!verify_merkle_proof(&root, leaves[2], 0, &[leaves[1], right]));
+        assert!(!verify_merkle_proof(&root, leaves[0], 1, &[leaves[1], right]));
+        assert!(!verify_merkle_proof(&root, leaves[0], 4, &[leaves[1], right]));
+        assert!(!verify_merkle_proof(&root, leaves[0], 0, &[leaves[1]]));
+
+        // Un solo movimiento: la raíz es la hoja
+        assert!(verify_merkle_proof(&leaves[0], leaves[0], 0, &[]));
+        assert!(!verify_merkle_proof(&leaves[0], leaves[0], 1, &[]));
+    }
+
+    #[test]
+    fn knockout_rounds_round_up() {
+        assert_eq!(knockout_rounds(2), 1);
+        assert_eq!(knockout_rounds(3), 2);
+        assert_eq!(knockout_rounds(4), 2);
+        assert_eq!(knockout_rounds(5), 3);
+        assert_eq!(knockout_rounds(8), 3);
+        assert_eq!(knockout_rounds(9), 4);
+        assert_eq!(knockout_rounds(MAX_TOURNAMENT_PLAYERS), 5);
+    }
 }